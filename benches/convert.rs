@@ -0,0 +1,48 @@
+//! Conversion throughput on generated notes of a few sizes.
+//!
+//! Run with `cargo bench --bench convert`, adding `-- --save-baseline before`
+//! and then `-- --baseline before` to compare a change against it.
+
+use std::fmt::Write;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// A note made of `sections` sections, each with a heading, a paragraph with
+/// emphasis and links, a nested list, tasks, a code block and a table.
+fn document(sections: usize) -> String {
+    let mut doc = String::from("---\ntitle: Benchmark\ntags: [bench, notes]\n---\n\n");
+    for n in 1..=sections {
+        write!(
+            doc,
+            "## Section {n}\n\n\
+             Some **bold** and *italic* text, a [link](https://example.com/{n}), a \
+             [[Note {n}|wikilink]], `inline code` and a footnote[^{n}].\n\
+             It continues with ~~struck~~ text and an ![image](img/{n}.png).\n\n\
+             - First item\n  - Nested item with a [note link](notes/{n}.md)\n- Second item\n\n\
+             1. One\n2. Two\n\n\
+             - [ ] Open task #todo\n- [x] Done task\n\n\
+             > A quote with *emphasis*.\n\n\
+             ```rust\nfn section_{n}() -> usize {{\n    {n}\n}}\n```\n\n\
+             | Name | Value |\n| ---- | ----: |\n| a | {n} |\n\n\
+             [^{n}]: Footnote {n}.\n\n"
+        )
+        .unwrap();
+    }
+    doc
+}
+
+fn bench_convert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert");
+    for (name, sections) in [("small", 1), ("medium", 20), ("large", 500)] {
+        let doc = document(sections);
+        group.throughput(Throughput::Bytes(doc.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &doc, |b, doc| {
+            b.iter(|| md2norg::convert(black_box(doc)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_convert);
+criterion_main!(benches);
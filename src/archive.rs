@@ -0,0 +1,103 @@
+//! Converting the notes in a zip archive, such as a vault export, without
+//! unpacking it first.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use md2norg::ConvertOptions;
+use zip::ZipArchive;
+
+use crate::{convert_source, Args, PathFilter, Summary};
+
+/// Whether `input` is a zip archive to convert the notes in.
+pub fn is_zip(input: &Path) -> bool {
+    input.is_file()
+        && input
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// The markdown files in the archive at `archive`, as paths under it, such as
+/// `vault.zip/notes/a.md`, in order. The whole archive is searched, as with
+/// `--recursive`, skipping hidden directories unless they're included.
+pub fn find_files(archive: &Path, args: &Args, filter: &PathFilter) -> Result<Vec<PathBuf>> {
+    let mut zip = open(archive)?;
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i)?;
+        // Entries that would be written outside the output directory, like
+        // `../a.md`, are skipped.
+        let Some(name) = entry.enclosed_name().filter(|_| !entry.is_dir()) else {
+            continue;
+        };
+        let hidden = name
+            .parent()
+            .into_iter()
+            .flat_map(Path::iter)
+            .any(|dir| dir.to_string_lossy().starts_with('.'));
+        let path = archive.join(name);
+        if (args.include_hidden || !hidden)
+            && args.is_markdown(&path)
+            && filter.allows(&path, archive)
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Converts the `files` found in `archive` by [`find_files`], each paired with
+/// the path to write it to, adding each to the `summary`. With `--fail-fast`,
+/// the rest are skipped once one has failed.
+pub fn convert_files(
+    archive: &Path,
+    files: &[(PathBuf, PathBuf)],
+    args: &Args,
+    options: &ConvertOptions,
+    summary: &mut Summary,
+) -> Result<()> {
+    let mut outputs: HashMap<&Path, &Path> = files
+        .iter()
+        .map(|(path, output_path)| (path.as_path(), output_path.as_path()))
+        .collect();
+    let bar = ProgressBar::hidden();
+    let mut zip = open(archive)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let Some(path) = entry.enclosed_name().map(|name| archive.join(name)) else {
+            continue;
+        };
+        let Some(output_path) = outputs.remove(path.as_path()) else {
+            continue;
+        };
+        if args.fail_fast && summary.failed > 0 {
+            summary.skipped += 1;
+            continue;
+        }
+        let mut bytes = Vec::new();
+        let result = entry
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("could not read {}", path.display()))
+            .and_then(|_| convert_source(&path, bytes, output_path, args, options, &bar));
+        match result {
+            Ok(Some(report)) => summary.converted.push(report),
+            Ok(None) => {}
+            Err(err) => {
+                log::error!("Failed to convert {}: {:#}", path.display(), err);
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open(archive: &Path) -> Result<ZipArchive<File>> {
+    let file =
+        File::open(archive).with_context(|| format!("could not read {}", archive.display()))?;
+    ZipArchive::new(file).with_context(|| format!("could not read {}", archive.display()))
+}
@@ -0,0 +1,101 @@
+//! How the conversion classified each block of a document, for working out
+//! why a line converted the way it did.
+
+use std::fmt;
+
+/// A block of a document, as the conversion classified it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub kind: BlockKind,
+    /// The block's first line, as converted. Empty for metadata, rules and
+    /// code, math and comment blocks.
+    pub text: String,
+}
+
+/// What a [`Block`] was taken to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockKind {
+    /// The frontmatter, or a `@document.meta` block added to the document.
+    Metadata,
+    Heading {
+        level: usize,
+    },
+    /// A bulleted or numbered list item, at a 1-based depth.
+    ListItem {
+        depth: usize,
+        ordered: bool,
+    },
+    Task {
+        depth: usize,
+    },
+    /// Text indented under a list item, which belongs to it.
+    ListContinuation,
+    Quote {
+        depth: usize,
+    },
+    Table {
+        rows: usize,
+    },
+    Definition,
+    Footnote,
+    Rule,
+    /// A line of ordinary text.
+    Text,
+    Code {
+        language: String,
+        lines: usize,
+    },
+    Math {
+        lines: usize,
+    },
+    Comment {
+        lines: usize,
+    },
+}
+
+impl Block {
+    pub(crate) fn new(kind: BlockKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = |n: usize| if n == 1 { "line" } else { "lines" };
+        match &self.kind {
+            BlockKind::Metadata => write!(f, "metadata")?,
+            BlockKind::Heading { level } => write!(f, "heading level {}", level)?,
+            BlockKind::ListItem {
+                depth,
+                ordered: false,
+            } => write!(f, "list item depth {}", depth)?,
+            BlockKind::ListItem {
+                depth,
+                ordered: true,
+            } => write!(f, "ordered list item depth {}", depth)?,
+            BlockKind::Task { depth } => write!(f, "task depth {}", depth)?,
+            BlockKind::ListContinuation => write!(f, "list continuation")?,
+            BlockKind::Quote { depth } => write!(f, "quote depth {}", depth)?,
+            BlockKind::Table { rows } => write!(f, "table rows={}", rows)?,
+            BlockKind::Definition => write!(f, "definition")?,
+            BlockKind::Footnote => write!(f, "footnote")?,
+            BlockKind::Rule => write!(f, "rule")?,
+            BlockKind::Text => write!(f, "text")?,
+            BlockKind::Code { language, lines: n } if language.is_empty() => {
+                write!(f, "code block ({} {})", n, lines(*n))?
+            }
+            BlockKind::Code { language, lines: n } => {
+                write!(f, "code block lang={} ({} {})", language, n, lines(*n))?
+            }
+            BlockKind::Math { lines: n } => write!(f, "math block ({} {})", n, lines(*n))?,
+            BlockKind::Comment { lines: n } => write!(f, "comment ({} {})", n, lines(*n))?,
+        }
+        if !self.text.is_empty() {
+            write!(f, ": {}", self.text)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! Default options read from a `md2norg.toml` file.
+//!
+//! Every setting is optional, and a flag given on the command line always
+//! takes precedence over the file:
+//!
+//! ```toml
+//! recursive = true
+//! output = "norg"
+//! extensions = ["md", "markdown"]
+//! overwrite = false
+//!
+//! [conversions]
+//! tags = false
+//! html = true
+//! add_meta = true
+//! collapse_blank_lines = true
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The config file looked for in the current directory when `--config`
+/// isn't given.
+const DEFAULT_PATH: &str = "md2norg.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub recursive: Option<bool>,
+    pub output: Option<String>,
+    pub extensions: Option<Vec<String>>,
+    /// Whether existing output files are overwritten.
+    pub overwrite: Option<bool>,
+    pub conversions: Conversions,
+}
+
+/// Which of the optional conversions are enabled.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Conversions {
+    pub tags: Option<bool>,
+    pub html: Option<bool>,
+    pub add_meta: Option<bool>,
+    pub collapse_blank_lines: Option<bool>,
+}
+
+/// Reads the config file at `path`, or `md2norg.toml` in the current
+/// directory if there is one.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(DEFAULT_PATH).is_file() => Path::new(DEFAULT_PATH),
+        None => return Ok(Config::default()),
+    };
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("could not read config {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))
+}
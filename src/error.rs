@@ -0,0 +1,41 @@
+//! Errors from converting documents.
+
+use std::io;
+use std::path::PathBuf;
+use std::string::FromUtf8Error;
+
+use thiserror::Error;
+
+/// Why a document couldn't be converted.
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    /// The input file couldn't be read.
+    #[error("could not read {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The input file isn't UTF-8 text.
+    #[error("could not read {}: not valid UTF-8", path.display())]
+    Utf8 {
+        path: PathBuf,
+        #[source]
+        source: FromUtf8Error,
+    },
+
+    /// The converted document, or the directory it goes in, couldn't be
+    /// written.
+    #[error("could not write {}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Part of the document couldn't be converted faithfully, when
+    /// converting strictly.
+    #[error("malformed markdown at line {line}: {message}")]
+    Malformed { line: usize, message: String },
+}
@@ -0,0 +1,131 @@
+//! Escaping of literal characters that Neorg would read as markup.
+//!
+//! Markdown has no use for `/`, `-`, `!` and the like, but around some text
+//! Neorg reads them as inline markup: `a /b/ c` is italic. Runs after the
+//! inline conversions, whose own markup is still held in placeholders, so
+//! anything matched here is literal text.
+
+use crate::LINK_TARGET_PREFIXES;
+
+/// The characters that open and close Neorg's inline markup.
+const ATTACHED_MODIFIERS: &[char] = &['*', '/', '_', '-', '!', '^', ',', '%', '&', '$', '`'];
+
+/// Escapes the characters on each line of `content` that Neorg would read as
+/// markup: a pair of the same modifier around some text, or braces that would
+/// make a link. The links made by the conversions are left as they are.
+///
+/// A pair must enclose a letter or digit, so table delimiter rows such as
+/// `|---|---|` are untouched.
+pub(crate) fn escape_markup(content: &str) -> String {
+    content
+        .split('\n')
+        .map(escape_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn escape_line(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut escaped = vec![false; chars.len()];
+    let mut skipped = vec![false; chars.len()];
+
+    let mut i = 0;
+    while i < chars.len() {
+        // HTML is converted later, so its attributes are left alone.
+        if chars[i] == '<'
+            && chars
+                .get(i + 1)
+                .is_some_and(|c| c.is_alphabetic() || *c == '/')
+        {
+            if let Some(end) = (i + 1..chars.len()).find(|&j| chars[j] == '>') {
+                skipped[i..=end].fill(true);
+                i = end;
+            }
+        } else if chars[i] == '{' {
+            if let Some(end) = (i + 1..chars.len()).find(|&j| chars[j] == '}') {
+                let target: String = chars[i + 1..end].iter().collect();
+                let is_link = chars.get(end + 1) == Some(&'[')
+                    || LINK_TARGET_PREFIXES
+                        .iter()
+                        .any(|prefix| target.starts_with(prefix));
+                if is_link {
+                    skipped[i..=end].fill(true);
+                } else {
+                    escaped[i] = true;
+                    escaped[end] = true;
+                }
+                i = end;
+            }
+        }
+        i += 1;
+    }
+
+    let boundary = |c: Option<&char>| c.is_none_or(|c| !c.is_alphanumeric());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let opens = ATTACHED_MODIFIERS.contains(&c)
+            && !skipped[i]
+            && boundary(i.checked_sub(1).map(|j| &chars[j]))
+            && chars
+                .get(i + 1)
+                .is_some_and(|next| !next.is_whitespace() && *next != c);
+        let close = opens
+            .then(|| {
+                (i + 2..chars.len())
+                    .take_while(|&j| !skipped[j])
+                    .find(|&j| {
+                        chars[j] == c
+                            && !chars[j - 1].is_whitespace()
+                            && boundary(chars.get(j + 1))
+                            && chars[i + 1..j].iter().any(|c| c.is_alphanumeric())
+                    })
+            })
+            .flatten();
+        match close {
+            Some(j) => {
+                escaped[i] = true;
+                escaped[j] = true;
+                i = j + 1;
+            }
+            None => i += 1,
+        }
+    }
+
+    let mut result = String::with_capacity(line.len());
+    for (c, escaped) in chars.into_iter().zip(escaped) {
+        if escaped {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_pairs() {
+        assert_eq!(escape_markup("a /b/ c"), "a \\/b\\/ c");
+        assert_eq!(escape_markup("/usr/local/"), "\\/usr/local\\/");
+        assert_eq!(escape_markup("and/or, 5 * 3 * 2"), "and/or, 5 * 3 * 2");
+        assert_eq!(escape_markup("see https://a.com/b"), "see https://a.com/b");
+        assert_eq!(escape_markup("|---|:-:|"), "|---|:-:|");
+        assert_eq!(
+            escape_markup("<a href=\"/b/\">/b/</a>"),
+            "<a href=\"/b/\">\\/b\\/</a>"
+        );
+    }
+
+    #[test]
+    fn test_escape_braces() {
+        assert_eq!(escape_markup("a set {x, y}"), "a set \\{x, y\\}");
+        assert_eq!(
+            escape_markup("{https://a.com/-b-}[a /c/]"),
+            "{https://a.com/-b-}[a \\/c\\/]"
+        );
+        assert_eq!(escape_markup("note{^ 1} {:b/c:}"), "note{^ 1} {:b/c:}");
+    }
+}
@@ -0,0 +1,200 @@
+//! YAML frontmatter, as written by Obsidian and most static site generators.
+//!
+//! Only the small subset of YAML that actually shows up in note frontmatter is
+//! understood: `key: value` pairs, inline `[a, b]` lists, and block lists of
+//! `- item` lines. Anything else is skipped rather than guessed at.
+
+/// A frontmatter value: either a single scalar or a list of scalars.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+/// Metadata collected from a frontmatter block.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Frontmatter {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub created: Option<String>,
+    pub updated: Option<String>,
+    /// Keys without a Neorg equivalent, in their original order.
+    pub extra: Vec<(String, Value)>,
+}
+
+/// Splits a leading `---` delimited frontmatter block off `content`, returning
+/// the parsed frontmatter (if there was any) and the rest of the document.
+pub(crate) fn split(content: &str) -> (Option<Frontmatter>, &str) {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (None, content);
+    }
+
+    let mut offset = content.find('\n').map_or(content.len(), |i| i + 1);
+    let body_start = offset;
+    for line in lines {
+        let end = offset + line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            let yaml = &content[body_start..offset];
+            return (Some(parse(yaml)), &content[end..]);
+        }
+        offset = end;
+    }
+
+    (None, content)
+}
+
+/// Parses the YAML between the frontmatter fences.
+fn parse(yaml: &str) -> Frontmatter {
+    let mut frontmatter = Frontmatter::default();
+    let mut lines = yaml.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') || line.starts_with(' ') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        let value = if value.is_empty() {
+            // A block list, one `- item` per following indented line.
+            let mut items = Vec::new();
+            while let Some(item) = lines
+                .peek()
+                .and_then(|next| next.trim_start().strip_prefix("- "))
+            {
+                items.push(unquote(item.trim()).to_string());
+                lines.next();
+            }
+            if items.is_empty() {
+                continue;
+            }
+            Value::List(items)
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Value::List(
+                inner
+                    .split(',')
+                    .map(|item| unquote(item.trim()).to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            )
+        } else {
+            Value::Scalar(unquote(value).to_string())
+        };
+
+        frontmatter.insert(key, value);
+    }
+
+    frontmatter
+}
+
+impl Frontmatter {
+    fn insert(&mut self, key: &str, value: Value) {
+        match (key.to_lowercase().as_str(), value) {
+            ("title", Value::Scalar(v)) => self.title = Some(v),
+            ("description" | "summary", Value::Scalar(v)) => self.description = Some(v),
+            ("author" | "authors", v) => self.authors.extend(v.into_items()),
+            ("tags" | "tag" | "categories" | "category", v) => {
+                self.categories.extend(v.into_items())
+            }
+            ("created" | "date", Value::Scalar(v)) => self.created = Some(v),
+            ("updated" | "modified" | "lastmod", Value::Scalar(v)) => self.updated = Some(v),
+            (_, v) => self.extra.push((key.to_string(), v)),
+        }
+    }
+
+    /// Renders the metadata as a Neorg `@document.meta` block.
+    pub fn to_document_meta(&self) -> String {
+        let mut meta = String::from("@document.meta\n");
+        push_scalar(&mut meta, "title", self.title.as_deref());
+        push_scalar(&mut meta, "description", self.description.as_deref());
+        push_list(&mut meta, "authors", &self.authors);
+        push_list(&mut meta, "categories", &self.categories);
+        push_scalar(&mut meta, "created", self.created.as_deref());
+        push_scalar(&mut meta, "updated", self.updated.as_deref());
+        for (key, value) in &self.extra {
+            match value {
+                Value::Scalar(v) => push_scalar(&mut meta, key, Some(v)),
+                Value::List(items) => push_list(&mut meta, key, items),
+            }
+        }
+        meta.push_str("@end\n");
+        meta
+    }
+}
+
+impl Value {
+    fn into_items(self) -> Vec<String> {
+        match self {
+            Value::Scalar(v) => v
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect(),
+            Value::List(items) => items,
+        }
+    }
+}
+
+fn push_scalar(meta: &mut String, key: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        meta.push_str(&format!("{}: {}\n", key, value));
+    }
+}
+
+fn push_list(meta: &mut String, key: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    meta.push_str(&format!("{}: [\n", key));
+    for item in items {
+        meta.push_str(&format!("  {}\n", item));
+    }
+    meta.push_str("]\n");
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_without_frontmatter() {
+        let content = "# Title\n---\n";
+        assert_eq!(split(content), (None, content));
+    }
+
+    #[test]
+    fn test_split_unterminated_frontmatter() {
+        let content = "---\ntitle: Nope\n";
+        assert_eq!(split(content), (None, content));
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        let (frontmatter, body) =
+            split("---\ntags: [a, \"b\"]\naliases:\n  - one\n  - two\n---\nBody");
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(frontmatter.categories, vec!["a", "b"]);
+        assert_eq!(
+            frontmatter.extra,
+            vec![(
+                "aliases".to_string(),
+                Value::List(vec!["one".to_string(), "two".to_string()])
+            )]
+        );
+        assert_eq!(body, "Body");
+    }
+}
@@ -0,0 +1,3355 @@
+//! Conversion of Markdown documents to Neorg.
+//!
+//! The [`convert`] function converts a document held in memory, and can be
+//! used to embed the conversion in other tools. [`convert_file`] converts a
+//! file on disk. Use [`convert_with_options`] or [`convert_file_with_options`]
+//! to change how particular constructs are converted.
+//!
+//! Failures are reported as a [`ConvertError`].
+
+mod block;
+mod error;
+mod escape;
+mod frontmatter;
+mod options;
+mod stats;
+mod table;
+mod warning;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+pub use block::{Block, BlockKind};
+pub use error::ConvertError;
+pub use options::{ConvertOptions, LineEnding, Passes, DEFAULT_LANGUAGES, DEFAULT_TODO_STATUSES};
+pub use stats::Stats;
+pub use warning::Warning;
+
+use once_cell::sync::Lazy;
+use regex::{NoExpand, Regex};
+
+// An ATX heading. The `#`s must be followed by whitespace (or nothing), so
+// that `#idea` at the start of a line stays a tag rather than a heading.
+static HEADING_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(#+)(?:[ \t]+|$)(.*?)[ \t]*$").unwrap());
+// The deepest heading Neorg has. Deeper Markdown headings are clamped to it.
+const MAX_HEADING_LEVEL: usize = 6;
+// The optional closing `#`s of an ATX heading, as in `## Title ##`.
+static CLOSING_HASHES_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:^|[ \t]+)#+$").unwrap());
+static TASK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)[-*+] \[(.)\] (.*)$").unwrap());
+// The emoji dates and priorities the Obsidian Tasks plugin adds to tasks.
+static TASK_METADATA_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"[ \t]*(?:(📅|🗓|🛫|⏳|✅)\x{FE0F}?[ \t]*(\d{4}-\d{2}-\d{2})|(🔺|⏫|🔼|🔽|⏬)\x{FE0F}?)",
+    )
+    .unwrap()
+});
+// A bullet's marker has to be followed by whitespace, so emphasis starting a
+// line, `*like this*`, isn't taken for one.
+static BULLET_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)[-*+]\s+(.*)$").unwrap());
+static ORDERED_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\s*)\d{1,9}[.)]\s+(.*)$").unwrap());
+static RULE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^ {0,3}(?:(?:-[ \t]*){3,}|(?:\*[ \t]*){3,}|(?:_[ \t]*){3,})$").unwrap()
+});
+static SETEXT_UNDERLINE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^ {0,3}(=+|-+)[ \t]*$").unwrap());
+static QUOTE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*((?:>\s*)+)(.*)$").unwrap());
+// An Obsidian callout's or GitHub alert's first line, within its quote:
+// `[!TYPE]`, then for a callout an optional `+` or `-` to make it foldable and
+// an optional title.
+static CALLOUT_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\[!([A-Za-z-]+)\][+-]?[ \t]*(.*)$").unwrap());
+static DEFINITION_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^:[ \t]+(.*)$").unwrap());
+static OPEN_FENCE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([ \t]*)(`{3,}|~{3,})\s*([^`]*)").unwrap());
+// The opening or closing line of a Pandoc fenced div, `::: warning`.
+static DIV_FENCE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^ {0,3}:{3,}").unwrap());
+// Pandoc attributes at the end of a heading, `{#id .class key=value}`, or
+// `{-}` for an unnumbered one.
+static HEADING_ATTRIBUTES_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[ \t]*\{(?:[#.][^{}]*|-)\}$").unwrap());
+static HTML_COMMENT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"<!--\s*(.*?)\s*-->").unwrap());
+static HTML_BREAK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t]*<br\s*/?>[ \t]*").unwrap());
+// The start of a converted line that begins a block of its own: a heading,
+// list item, quote, footnote or ranged tag.
+static NEORG_BLOCK_START_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:[-~>*^$]+ |@)").unwrap());
+static NEORG_HEADING_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\*+\s").unwrap());
+// A converted list item, with its marker, or quote.
+static NEORG_ITEM_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:[-~]+|(>+))\s").unwrap());
+static HTML_TAG_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"</?([A-Za-z][A-Za-z0-9]*)(?:\s[^<>]*)?/?>").unwrap());
+// A backslash escaped ASCII punctuation character, noting whether it starts
+// its line.
+static ESCAPE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)(^[ \t]*)?\\([!-/:-@\[-`{-~])").unwrap());
+// Inline math: `$`s around text that doesn't start or end with a space. The
+// character before is captured to spot an escaped `$`, and the one after to
+// spot a closing `$` followed by a digit, which is more likely a price.
+static INLINE_MATH_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\\?)\$\$?([^\s$](?:[^$\n]*?[^\s$\\])?)\$\$?(\d?)").unwrap());
+static CODE_SPAN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"`[^`\n]+`").unwrap());
+static PLACEHOLDER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\u{2}(\d+)\u{3}").unwrap());
+// What's kept together when wrapping: a link with a description, whose target
+// has been protected, along with anything attached to it, or else a word.
+static WRAP_UNIT_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\S*?(?:\u{2}\d+\u{3}|\{[^{}\n]*\})\[[^\]\n]*\]\S*|\S+").unwrap());
+
+static REFERENCE_DEFINITION_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^ {0,3}\[([^\]^][^\]]*)\]:\s*(\S+)(?:\s+(?:"[^"]*"|'[^']*'|\([^)]*\)))?\s*$"#)
+        .unwrap()
+});
+static REFERENCE_LINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(!?)\[([^\]]*)\]\[([^\]]*)\]").unwrap());
+
+static FOOTNOTE_DEFINITION_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:[ \t]*(.*)$").unwrap());
+// A footnote reference, or the start of a definition when at the start of a
+// line and followed by a `:`.
+static FOOTNOTE_REFERENCE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)(^ {0,3})?\[\^([^\]\s]+)\](:?)").unwrap());
+static INLINE_FOOTNOTE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\^\[([^\]]+)\]").unwrap());
+
+// A converted link to a relative `.md` path, with an optional `#anchor`:
+// anything with a `:` is a URL or already a Neorg link of some other kind.
+static NOTE_LINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{([^{}:#\s]+)\.md(?:#([^{}]+))?\}").unwrap());
+
+// Obsidian tags: a `#` that isn't part of a word, followed by letters, digits,
+// `_`, `-` or `/`, at least one of which isn't a digit.
+static TAG_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)(^|[ \t(])#([\w/-]*[\p{L}_/-][\w/-]*)").unwrap());
+
+// Obsidian links: `[[Page]]`, `[[Page#Heading]]`, `[[#Heading]]`, each with an
+// optional `|Alias`, and embeds of the same form prefixed with `!`.
+static WIKILINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(!?)\[\[([^\]|#]*)(?:#([^\]|]+))?(?:\|([^\]]+))?\]\]").unwrap());
+
+// Obsidian block IDs, a `^id` ending a line to make its block linkable, or
+// alone on the line after a list or table.
+static BLOCK_ID_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:^|[ \t]+)\^[A-Za-z0-9-]+[ \t]*$").unwrap());
+
+// HTML elements whose tags are stripped, keeping their content. Anything else
+// in angle brackets is more likely to be text, like `Vec<String>`.
+const HTML_ELEMENTS: &[&str] = &[
+    "abbr", "big", "center", "cite", "del", "details", "div", "font", "ins", "kbd", "mark", "p",
+    "q", "s", "samp", "small", "span", "strike", "sub", "summary", "sup", "tt", "var",
+];
+
+// Characters Neorg gives a meaning anywhere in a line, as attached modifiers
+// or in links, and those it only does at the start of one.
+const NEORG_INLINE_SPECIAL: &str = "*/_-^,!|`%$&[]{}<>\\";
+const NEORG_LINE_START_SPECIAL: &str = "#~>@=+:";
+
+// Labels for GitHub's alert types, which Obsidian has as callout types too.
+const ALERT_LABELS: &[(&str, &str)] = &[
+    ("note", "Note"),
+    ("tip", "Tip"),
+    ("important", "Important"),
+    ("warning", "Warning"),
+    ("caution", "Caution"),
+];
+
+// Labels for the rest of Obsidian's callout types, including their aliases.
+const CALLOUT_LABELS: &[(&str, &str)] = &[
+    ("abstract", "Summary"),
+    ("summary", "Summary"),
+    ("tldr", "Summary"),
+    ("info", "Info"),
+    ("todo", "Todo"),
+    ("hint", "Tip"),
+    ("success", "Success"),
+    ("check", "Success"),
+    ("done", "Success"),
+    ("question", "Question"),
+    ("help", "Question"),
+    ("faq", "Question"),
+    ("attention", "Caution"),
+    ("failure", "Failure"),
+    ("fail", "Failure"),
+    ("missing", "Failure"),
+    ("danger", "Danger"),
+    ("error", "Danger"),
+    ("bug", "Bug"),
+    ("example", "Example"),
+    ("quote", "Quote"),
+    ("cite", "Quote"),
+];
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "avif"];
+
+// Typographic punctuation and the ASCII each is replaced with by
+// `normalize_punctuation`.
+const PUNCTUATION: &[(char, &str)] = &[
+    ('\u{2018}', "'"),   // left single quote
+    ('\u{2019}', "'"),   // right single quote, or apostrophe
+    ('\u{201C}', "\""),  // left double quote
+    ('\u{201D}', "\""),  // right double quote
+    ('\u{2013}', "-"),   // en dash
+    ('\u{2014}', "--"),  // em dash
+    ('\u{2026}', "..."), // ellipsis
+];
+
+// HTML character references: decimal, hexadecimal or named. The indent before
+// one starting a line is captured, as it might decode to a character that's
+// only special there.
+static ENTITY_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?m)(^[ \t]*)?&(?:#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6})|([A-Za-z][A-Za-z0-9]{1,31}));",
+    )
+    .unwrap()
+});
+
+// The named HTML entities that are decoded, the common ones in notes.
+const HTML_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("times", '×'),
+    ("divide", '÷'),
+    ("micro", 'µ'),
+    ("middot", '·'),
+    ("bull", '•'),
+    ("hellip", '…'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("sect", '§'),
+    ("para", '¶'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("euro", '€'),
+    ("yen", '¥'),
+    ("frac12", '½'),
+    ("frac14", '¼'),
+    ("frac34", '¾'),
+    ("larr", '←'),
+    ("rarr", '→'),
+    ("uarr", '↑'),
+    ("darr", '↓'),
+    ("harr", '↔'),
+    ("ne", '≠'),
+    ("le", '≤'),
+    ("ge", '≥'),
+    ("infin", '∞'),
+];
+
+// An Obsidian image size, a width with an optional height: `200` or `200x100`.
+static IMAGE_SIZE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+(?:x\d+)?$").unwrap());
+
+// The title at the end of an inline link's destination.
+static LINK_TITLE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^(.*?)\s+(?:"[^"]*"|'[^']*')$"#).unwrap());
+
+// A `{target}` and the `[` of its description, if it has one.
+static LINK_TARGET_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([^{}\n]*)\}(\[)?").unwrap());
+
+/// The starts of the link targets that conversions emit without a
+/// description: footnote references, and heading, image, file and note links.
+const LINK_TARGET_PREFIXES: &[&str] = &["^ ", "# ", "image:", "/ ", ":"];
+
+// Links, applied in order as (pattern, replacement) pairs, after inline links
+// and images, which are parsed by `convert_inline_links`.
+static LINK_CONVERSIONS: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+    compile_conversions(&[
+        // Automatic links
+        (r"<(https?://[^>]+)>", "{$1}[$1]"),
+        (
+            r"<([\w.!#$%&'*+/=?^`{|}~-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*)>",
+            "{mailto:$1}[$1]",
+        ),
+    ])
+});
+
+// Emphasis. Markup is converted to placeholders, so the single-marker italic
+// patterns can't mistake bold's delimiters for their own, and escaping can
+// tell it from literal text.
+static EMPHASIS_CONVERSIONS: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+    compile_conversions(&[
+        // Bold and italic together, including where one of them ends first
+        (
+            r"\*\*\*([^*\s](?:[^*\n]*?[^*\s])?)\*\*\*",
+            "\u{1}\u{4}$1\u{4}\u{1}",
+        ),
+        (
+            r"\*\*\*([^*\s](?:[^*\n]*?[^*\s])?)\*([^*\n]*?[^*\s])\*\*",
+            "\u{1}\u{4}$1\u{4}$2\u{1}",
+        ),
+        (
+            r"\*\*\*([^*\s](?:[^*\n]*?[^*\s])?)\*\*([^*\n]*?[^*\s])\*",
+            "\u{4}\u{1}$1\u{1}$2\u{4}",
+        ),
+        (
+            r"\b___([^_\s](?:[^_\n]*?[^_\s])?)___\b",
+            "\u{1}\u{4}$1\u{4}\u{1}",
+        ),
+        // Bold
+        (r"\*\*([^*\s](?:[^\n]*?\S)?)\*\*", "\u{1}$1\u{1}"),
+        (r"\b__([^_\s](?:[^\n]*?\S)?)__\b", "\u{1}$1\u{1}"),
+        // Italic
+        (r"\*([^*\s](?:[^*\n]*?[^*\s])?)\*", "\u{4}$1\u{4}"),
+        (r"\b_([^_\s](?:[^_\n]*?[^_\s])?)_\b", "\u{4}$1\u{4}"),
+        // Strikethrough
+        (r"~~([^~\s](?:[^~\n]*?[^~\s])?)~~", "\u{5}$1\u{5}"),
+        // Obsidian highlight, which Neorg has no markup for, so is underlined.
+        // Last, so the italic rules can't take its underscores.
+        (
+            r"(?m)(^|[^\w=\\])==([^=\s](?:[^=\n]*?[^=\s])?)==",
+            "${1}\u{6}${2}\u{6}",
+        ),
+    ])
+});
+
+// The placeholders used for inline markup, and the markup they stand for.
+const MARKUP_PLACEHOLDERS: [(char, char); 6] = [
+    ('\u{1}', '*'),
+    ('\u{4}', '/'),
+    ('\u{5}', '-'),
+    ('\u{6}', '_'),
+    ('\u{7}', ','),
+    ('\u{8}', '^'),
+];
+
+// Pandoc style `~subscript~` and `^superscript^`, which can't contain spaces.
+// Strikethrough's double tildes are converted first.
+static SUBSCRIPT_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)(^|[^~\\])~([^~\s]+)~").unwrap());
+static SUPERSCRIPT_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)(^|[^\^\\\[])\^([^\^\s\]]+)\^").unwrap());
+
+fn compile_conversions(conversions: &[(&str, &'static str)]) -> Vec<(Regex, &'static str)> {
+    conversions
+        .iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), *replacement))
+        .collect()
+}
+
+/// Converts a Markdown document to Neorg.
+pub fn convert(content: &str) -> Result<String, ConvertError> {
+    convert_markdown_to_neorg(content)
+}
+
+fn convert_markdown_to_neorg(content: &str) -> Result<String, ConvertError> {
+    convert_with_options(content, &ConvertOptions::default())
+}
+
+/// Converts a Markdown document to Neorg, with control over how particular
+/// constructs are converted.
+pub fn convert_with_options(
+    content: &str,
+    options: &ConvertOptions,
+) -> Result<String, ConvertError> {
+    convert_document(content, None, options).map(|conversion| conversion.output)
+}
+
+/// A converted document, with counts of what was converted.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub output: String,
+    pub stats: Stats,
+    /// The Markdown that couldn't be converted faithfully, in order.
+    pub warnings: Vec<Warning>,
+    /// How each block of the document was classified, in order.
+    pub blocks: Vec<Block>,
+}
+
+/// Converts a Markdown document as [`convert_with_options`] does, also
+/// counting what was converted and warning of anything that couldn't be.
+pub fn convert_with_stats(
+    content: &str,
+    options: &ConvertOptions,
+) -> Result<Conversion, ConvertError> {
+    convert_document(content, None, options)
+}
+
+/// Converts a Markdown document as [`convert_with_stats`] does, for a
+/// document named `name`, such as a file's name without its extension, which
+/// becomes its title when [`ConvertOptions::add_meta`] is set and it has no
+/// better one.
+pub fn convert_named(
+    content: &str,
+    name: &str,
+    options: &ConvertOptions,
+) -> Result<Conversion, ConvertError> {
+    convert_document(content, Some(name), options)
+}
+
+/// Converts a document, using `file_stem` as its title when
+/// [`ConvertOptions::add_meta`] is set and it has no better one.
+fn convert_document(
+    content: &str,
+    file_stem: Option<&str>,
+    options: &ConvertOptions,
+) -> Result<Conversion, ConvertError> {
+    // Everything is converted with `\n` line endings, and the ending wanted
+    // restored at the end.
+    let crlf = match options.line_ending {
+        LineEnding::Keep => uses_crlf(content),
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+    };
+    let document = &content.replace("\r\n", "\n");
+
+    let (mut frontmatter, content) = frontmatter::split(document);
+    let first_line = document[..document.len() - content.len()]
+        .matches('\n')
+        .count()
+        + 1;
+
+    let regions = split_regions(content);
+    if options.add_meta {
+        let frontmatter = frontmatter.get_or_insert_with(Default::default);
+        if frontmatter.title.is_none() {
+            frontmatter.title = first_title(&regions).or(file_stem).map(String::from);
+        }
+    }
+
+    let references = collect_references(&regions);
+    let warnings = warning::check(content, first_line, &references, options);
+    if let Some(warning) = warnings.first().filter(|_| options.strict) {
+        return Err(ConvertError::Malformed {
+            line: warning.line,
+            message: warning.message.clone(),
+        });
+    }
+    let mut context = Context {
+        options,
+        references,
+        footnotes: collect_footnotes(&regions),
+        inline_footnotes: Vec::new(),
+        tags: Vec::new(),
+        nesting: ListNesting::default(),
+        stats: Stats::default(),
+        blocks: Vec::new(),
+    };
+
+    let mut body = String::new();
+    for region in regions {
+        match region {
+            Region::Prose(lines) => {
+                let prose = convert_prose(&lines.join("\n"), &mut context);
+                if options.collapse_blank_lines {
+                    push_collapsed(&mut body, &prose);
+                } else {
+                    body.push_str(&prose);
+                }
+            }
+            Region::Code {
+                lines,
+                fence: Some((open, close)),
+                ..
+            } if !options.passes.code => {
+                context.blocks.push(Block::new(
+                    BlockKind::Code {
+                        language: String::new(),
+                        lines: lines.len(),
+                    },
+                    "",
+                ));
+                body.push_str(open);
+                body.push('\n');
+                for line in lines {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+                if let Some(close) = close {
+                    body.push_str(close);
+                    body.push('\n');
+                }
+            }
+            Region::Code { lines, .. } if !options.passes.code => {
+                context.blocks.push(Block::new(
+                    BlockKind::Code {
+                        language: String::new(),
+                        lines: lines.len(),
+                    },
+                    "",
+                ));
+                for line in lines {
+                    if !line.is_empty() {
+                        body.push_str("    ");
+                    }
+                    body.push_str(line);
+                    body.push('\n');
+                }
+            }
+            Region::Code {
+                language,
+                lines,
+                indent,
+                ..
+            } => {
+                context.stats.code_blocks += 1;
+                context.blocks.push(Block::new(
+                    BlockKind::Code {
+                        language: options.language(language).to_string(),
+                        lines: lines.len(),
+                    },
+                    "",
+                ));
+                // A fence indented under a list item is lined up with the
+                // item's text, and the list carries on after it.
+                let pad = match context.nesting.continuation_indent() {
+                    Some(width) if !indent.is_empty() => " ".repeat(width),
+                    _ => {
+                        context.nesting.reset();
+                        String::new()
+                    }
+                };
+                body.push_str(&pad);
+                body.push_str("@code");
+                if !language.is_empty() {
+                    body.push(' ');
+                    body.push_str(options.language(language));
+                }
+                body.push('\n');
+                for line in lines {
+                    let line = strip_indent(line, indent_width(indent));
+                    if !line.is_empty() {
+                        body.push_str(&pad);
+                    }
+                    body.push_str(line);
+                    body.push('\n');
+                }
+                body.push_str(&pad);
+                body.push_str("@end\n");
+            }
+            Region::Comment(lines) => {
+                context.nesting.reset();
+                context
+                    .blocks
+                    .push(Block::new(BlockKind::Comment { lines: lines.len() }, ""));
+                body.push_str("@comment\n");
+                for line in lines {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+                body.push_str("@end\n");
+            }
+            Region::Math(lines) => {
+                context.nesting.reset();
+                context
+                    .blocks
+                    .push(Block::new(BlockKind::Math { lines: lines.len() }, ""));
+                body.push_str("@math\n");
+                for line in lines {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+                body.push_str("@end\n");
+            }
+        }
+    }
+
+    // Inline footnotes have nowhere else to go, so are defined at the end.
+    if !context.inline_footnotes.is_empty() {
+        if !body.is_empty() {
+            if !body.ends_with('\n') {
+                body.push('\n');
+            }
+            body.push('\n');
+        }
+        for (label, text) in &context.inline_footnotes {
+            body.push_str(&format!("^ {}\n{}\n", label, text));
+        }
+    }
+
+    if !context.tags.is_empty() {
+        let categories = &mut frontmatter.get_or_insert_with(Default::default).categories;
+        for tag in context.tags {
+            if !categories.contains(&tag) {
+                categories.push(tag);
+            }
+        }
+    }
+
+    let mut result = String::new();
+    if let Some(frontmatter) = frontmatter {
+        context
+            .blocks
+            .insert(0, Block::new(BlockKind::Metadata, ""));
+        result.push_str(&frontmatter.to_document_meta());
+        if !body.is_empty() && !body.starts_with('\n') {
+            result.push('\n');
+        }
+    }
+    result.push_str(&body);
+
+    // Conversion works line by line, so always ends lines with a newline, and
+    // lines it drops, like reference definitions, can leave blank ones at the
+    // end. Keep the whole input's own ending instead, and a blank input, with
+    // nothing to convert, as it is. Collapsing blank lines leaves at most one
+    // newline there.
+    if document.trim().is_empty() && result.trim().is_empty() && !options.collapse_blank_lines {
+        result = document.to_string();
+    } else {
+        let mut newlines = document.len() - document.trim_end_matches('\n').len();
+        if options.collapse_blank_lines {
+            newlines = newlines.min(1);
+        }
+        result.truncate(result.trim_end_matches('\n').len());
+        result.push_str(&"\n".repeat(newlines));
+    }
+
+    if crlf {
+        result = result.replace('\n', "\r\n");
+    }
+
+    Ok(Conversion {
+        output: result,
+        stats: context.stats,
+        warnings,
+        blocks: context.blocks,
+    })
+}
+
+/// Whether most of the lines in `content` end with `\r\n`.
+fn uses_crlf(content: &str) -> bool {
+    let crlf = content.matches("\r\n").count();
+    crlf > content.matches('\n').count() - crlf
+}
+
+/// Converts the Markdown file at `input`, writing the Neorg document to
+/// `output` and creating its directory if needed.
+pub fn convert_file(input: &Path, output: &Path) -> Result<(), ConvertError> {
+    convert_file_with_options(input, output, &ConvertOptions::default()).map(|_| ())
+}
+
+/// Converts a Markdown file as [`convert_file`] does, with control over how
+/// particular constructs are converted. Returns the conversion that was
+/// written.
+pub fn convert_file_with_options(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<Conversion, ConvertError> {
+    let bytes = fs::read(input).map_err(|source| ConvertError::Read {
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let content = String::from_utf8(bytes).map_err(|source| ConvertError::Utf8 {
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let file_stem = input.file_stem().map(|stem| stem.to_string_lossy());
+    let conversion = convert_document(&content, file_stem.as_deref(), options)?;
+
+    // `create_dir_all` tolerates the directory appearing concurrently, so
+    // files in the same directory can be converted in parallel.
+    let write_error = |source| ConvertError::Write {
+        path: output.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    fs::write(output, &conversion.output).map_err(write_error)?;
+    Ok(conversion)
+}
+
+/// Appends converted prose to `body`, dropping any blank line that follows
+/// another. Code is appended as it is, so keeps all of its blank lines.
+fn push_collapsed(body: &mut String, prose: &str) {
+    for line in prose.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        if blank && (body.is_empty() || body.ends_with("\n\n")) {
+            continue;
+        }
+        body.push_str(line);
+    }
+}
+
+/// State shared by the conversion of every prose region in a document.
+struct Context<'a> {
+    options: &'a ConvertOptions,
+    references: References,
+    /// Labels of the footnotes defined in the document.
+    footnotes: HashSet<String>,
+    /// Labels and text of `^[...]` footnotes, to be defined at the end.
+    inline_footnotes: Vec<(String, String)>,
+    /// Obsidian tags found in the document, in order of first appearance.
+    tags: Vec<String>,
+    /// The list going at the end of the last prose region, which code
+    /// indented under one of its items belongs to.
+    nesting: ListNesting,
+    stats: Stats,
+    blocks: Vec<Block>,
+}
+
+/// A run of consecutive lines that are either ordinary Markdown or the body of
+/// a code block.
+#[derive(Debug)]
+enum Region<'a> {
+    Prose(Vec<&'a str>),
+    Code {
+        language: &'a str,
+        lines: Vec<&'a str>,
+        /// The opening and closing lines of a fenced block, as written. The
+        /// closing line is missing when the block runs to the end of the
+        /// document.
+        fence: Option<(&'a str, Option<&'a str>)>,
+        /// The indentation of a fenced block's opening line, which is taken
+        /// off its lines too.
+        indent: &'a str,
+    },
+    /// The text of an HTML comment spanning several lines.
+    Comment(Vec<&'a str>),
+    /// The body of a `$$` display math block.
+    Math(Vec<&'a str>),
+}
+
+/// Splits a document into prose, code and comment regions, so that the
+/// Markdown conversions are only ever run over prose. Code is either fenced,
+/// or indented by four spaces (or a tab) after a blank line, outside of a
+/// list. A code fence that is never closed runs to the end of the document,
+/// as in CommonMark, while a comment or math block that is never closed is
+/// left as prose.
+fn split_regions(content: &str) -> Vec<Region<'_>> {
+    let mut regions = Vec::new();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut lines = content.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(code_line) = strip_code_indent(line).filter(|_| starts_indented_code(&prose)) {
+            let mut code = vec![code_line];
+            while let Some(next) = lines.peek() {
+                if let Some(code_line) = strip_code_indent(next) {
+                    code.push(code_line);
+                } else if next.trim().is_empty() {
+                    code.push("");
+                } else {
+                    break;
+                }
+                lines.next();
+            }
+
+            let trailing_blank_lines = code.iter().rev().take_while(|l| l.is_empty()).count();
+            let blank_lines = code.split_off(code.len() - trailing_blank_lines);
+            if !prose.is_empty() {
+                regions.push(Region::Prose(std::mem::take(&mut prose)));
+            }
+            regions.push(Region::Code {
+                language: "",
+                lines: code,
+                fence: None,
+                indent: "",
+            });
+            prose.extend(blank_lines);
+            continue;
+        }
+
+        if let Some(opening) = line.trim().strip_prefix("$$") {
+            let mut math = Vec::new();
+            let closed = if let Some(body) = opening.strip_suffix("$$") {
+                math.push(body);
+                true
+            } else {
+                math.push(opening);
+                let mut closed = false;
+                for math_line in lines.by_ref() {
+                    if let Some(body) = math_line.trim_end().strip_suffix("$$") {
+                        math.push(body);
+                        closed = true;
+                        break;
+                    }
+                    math.push(math_line);
+                }
+                closed
+            };
+
+            if closed {
+                if !prose.is_empty() {
+                    regions.push(Region::Prose(std::mem::take(&mut prose)));
+                }
+                math.retain(|line| !line.trim().is_empty());
+                regions.push(Region::Math(math.into_iter().map(str::trim).collect()));
+            } else {
+                prose.push(line);
+                prose.extend(&math[1..]);
+            }
+            continue;
+        }
+
+        if let Some(opening) = line
+            .trim_start()
+            .strip_prefix("<!--")
+            .filter(|rest| !rest.contains("-->"))
+        {
+            let mut comment = vec![opening];
+            let mut rest = None;
+            for comment_line in lines.by_ref() {
+                if let Some((text, after)) = comment_line.split_once("-->") {
+                    comment.push(text);
+                    rest = Some(after);
+                    break;
+                }
+                comment.push(comment_line);
+            }
+
+            match rest {
+                Some(rest) => {
+                    if !prose.is_empty() {
+                        regions.push(Region::Prose(std::mem::take(&mut prose)));
+                    }
+                    comment.retain(|line| !line.trim().is_empty());
+                    regions.push(Region::Comment(
+                        comment.into_iter().map(str::trim).collect(),
+                    ));
+                    if !rest.trim().is_empty() {
+                        prose.push(rest.trim_start());
+                    }
+                }
+                None => {
+                    prose.push(line);
+                    prose.extend(&comment[1..]);
+                }
+            }
+            continue;
+        }
+
+        // Neorg has nothing like a div, so only the content is kept.
+        if DIV_FENCE_REGEX.is_match(line) {
+            continue;
+        }
+
+        let Some(caps) = OPEN_FENCE_REGEX.captures(line) else {
+            prose.push(line);
+            continue;
+        };
+
+        let indent = caps.get(1).unwrap().as_str();
+        let fence = caps.get(2).unwrap().as_str();
+        let language = fence_language(caps.get(3).map_or("", |m| m.as_str()));
+        let mut code = Vec::new();
+        let mut closing = None;
+        for code_line in lines.by_ref() {
+            if closes_fence(code_line, fence) {
+                closing = Some(code_line);
+                break;
+            }
+            code.push(code_line);
+        }
+
+        if !prose.is_empty() {
+            regions.push(Region::Prose(std::mem::take(&mut prose)));
+        }
+        regions.push(Region::Code {
+            language,
+            lines: code,
+            fence: Some((line, closing)),
+            indent,
+        });
+    }
+
+    if !prose.is_empty() {
+        regions.push(Region::Prose(prose));
+    }
+
+    regions
+}
+
+/// The language of a fenced code block from its info string: the first word,
+/// as in `rust {.numberLines}`, or, when it's all Pandoc attributes, as in
+/// `{.rust .numberLines}` or `{=html}`, the first class or raw format.
+fn fence_language(info: &str) -> &str {
+    let info = info.trim();
+    match info.strip_prefix('{') {
+        Some(attributes) => attributes
+            .trim_end_matches('}')
+            .split_whitespace()
+            .find_map(|attribute| {
+                attribute
+                    .strip_prefix('.')
+                    .or_else(|| attribute.strip_prefix('='))
+            })
+            .unwrap_or(""),
+        None => info
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or(""),
+    }
+}
+
+/// The content of an indented code line, without its indentation.
+fn strip_code_indent(line: &str) -> Option<&str> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    line.strip_prefix("    ")
+        .or_else(|| line.strip_prefix('\t'))
+}
+
+/// Whether an indented line following the `prose` so far would start a code
+/// block: it has to follow a blank line, and not be part of a list item.
+fn starts_indented_code(prose: &[&str]) -> bool {
+    if prose.last().is_some_and(|line| !line.trim().is_empty()) {
+        return false;
+    }
+    let Some(previous) = prose.iter().rev().find(|line| !line.trim().is_empty()) else {
+        return true;
+    };
+    !(previous.starts_with([' ', '\t'])
+        || BULLET_REGEX.is_match(previous)
+        || ORDERED_REGEX.is_match(previous))
+}
+
+/// Whether `line` closes a code block opened with `fence`: it must be made of
+/// the same character, and be at least as long.
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    let marker = fence.chars().next().unwrap();
+    line.len() >= fence.len() && line.chars().all(|c| c == marker)
+}
+
+/// Reference link definitions (`[label]: url "title"`), keyed by their
+/// normalised label.
+type References = HashMap<String, String>;
+
+/// Collects the reference link definitions from the prose of a document, so
+/// that reference links can be resolved wherever they appear. Only labels
+/// that some reference link or image uses are collected, so a `[x]: y` line
+/// that nothing refers to is kept as text.
+fn collect_references(regions: &[Region]) -> References {
+    let prose = regions.iter().filter_map(|region| match region {
+        Region::Prose(lines) => Some(lines),
+        _ => None,
+    });
+    let mut used = HashSet::new();
+    let mut references = References::new();
+    for lines in prose.clone() {
+        for line in lines {
+            used.extend(REFERENCE_LINK_REGEX.captures_iter(line).map(|caps| {
+                let label = if caps[3].is_empty() {
+                    &caps[2]
+                } else {
+                    &caps[3]
+                };
+                normalize_label(label)
+            }));
+        }
+    }
+    for lines in prose {
+        for caps in lines
+            .iter()
+            .filter_map(|line| REFERENCE_DEFINITION_REGEX.captures(line))
+        {
+            let label = normalize_label(&caps[1]);
+            if used.contains(&label) {
+                // As in CommonMark, the first definition of a label wins.
+                references
+                    .entry(label)
+                    .or_insert_with(|| caps[2].to_string());
+            }
+        }
+    }
+    references
+}
+
+/// Whether `line` defines a reference that's used in the document, and so
+/// is inlined into the links using it rather than kept.
+fn is_used_definition(line: &str, references: &References) -> bool {
+    REFERENCE_DEFINITION_REGEX
+        .captures(line)
+        .is_some_and(|caps| references.contains_key(&normalize_label(&caps[1])))
+}
+
+/// The text of the document's first level one heading, if it has one.
+fn first_title<'a>(regions: &[Region<'a>]) -> Option<&'a str> {
+    regions
+        .iter()
+        .filter_map(|region| match region {
+            Region::Prose(lines) => Some(lines),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|line| {
+            HEADING_REGEX
+                .captures(line)
+                .filter(|caps| caps[1].len() == 1)
+                .map(|caps| heading_text(caps.get(2).unwrap().as_str()))
+        })
+}
+
+/// The text of an ATX heading, without any closing `#`s or attributes.
+fn heading_text(text: &str) -> &str {
+    let text = without_attributes(text);
+    match CLOSING_HASHES_REGEX.find(text) {
+        Some(closing) => &text[..closing.start()],
+        None => text,
+    }
+}
+
+/// A heading's text without any Pandoc attributes, which Neorg has no
+/// equivalent of.
+fn without_attributes(text: &str) -> &str {
+    match HEADING_ATTRIBUTES_REGEX.find(text) {
+        Some(attributes) => &text[..attributes.start()],
+        None => text,
+    }
+}
+
+fn collect_footnotes(regions: &[Region]) -> HashSet<String> {
+    regions
+        .iter()
+        .filter_map(|region| match region {
+            Region::Prose(lines) => Some(lines),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|line| FOOTNOTE_DEFINITION_REGEX.captures(line))
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Reference labels match case-insensitively and regardless of whitespace.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Replaces `[text][label]` and `[label][]` links, and `![alt][label]`
+/// images, with inline Neorg links to the URL defined for the label. Links to
+/// undefined labels are left as-is.
+fn resolve_reference_links(content: &str, references: &References, stats: &mut Stats) -> String {
+    REFERENCE_LINK_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let image = !caps[1].is_empty();
+            let text = &caps[2];
+            let label = if caps[3].is_empty() { text } else { &caps[3] };
+            match references.get(&normalize_label(label)) {
+                Some(url) if !label.is_empty() => {
+                    stats.links += 1;
+                    if image {
+                        let (alt, size) = split_image_size(text);
+                        format!("{{image:{}}}[{}]", url, image_description(alt, size))
+                    } else {
+                        format!("{{{}}}[{}]", url, text)
+                    }
+                }
+                _ => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Converts a stretch of Markdown that contains no fenced code.
+fn convert_prose(content: &str, context: &mut Context) -> String {
+    let mut result = String::new();
+
+    let passes = context.options.passes;
+
+    // Definitions are inlined into the links that use them, so aren't needed
+    // in the output.
+    let content = content
+        .split('\n')
+        .filter(|line| !passes.links || !is_used_definition(line, &context.references))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let content = if passes.links {
+        strip_block_ids(&content)
+    } else {
+        content
+    };
+    let content = convert_hard_breaks(&content);
+
+    let (content, mut code_spans) = protect_inline_code(&content);
+    let content = protect_inline_math(&content, &mut code_spans);
+    let content = protect_escapes(&content, &mut code_spans);
+    let mut content = convert_html_comments(&content, &mut code_spans);
+    if context.options.decode_entities {
+        content = decode_entities(&content, &mut code_spans);
+    }
+    if context.options.normalize_punctuation {
+        content = normalize_punctuation(&content);
+    }
+    if passes.links {
+        content = convert_inline_links(&content, &mut context.stats);
+        for (re, replacement) in LINK_CONVERSIONS.iter() {
+            context.stats.links += re.find_iter(&content).count();
+            content = re.replace_all(&content, *replacement).to_string();
+        }
+        content = resolve_reference_links(&content, &context.references, &mut context.stats);
+        if context.options.rewrite_md_links {
+            content = convert_note_links(&content);
+        }
+        content = convert_wikilinks(&content, &mut context.stats);
+        content = protect_link_targets(&content, &mut code_spans);
+    }
+    if context.options.tags {
+        content = collect_tags(&content, &mut context.tags);
+    }
+    if passes.emphasis {
+        for (re, replacement) in EMPHASIS_CONVERSIONS.iter() {
+            content = re.replace_all(&content, *replacement).to_string();
+        }
+        content = convert_scripts(&content);
+    }
+    let queued_footnotes = context.inline_footnotes.len();
+    let content = convert_footnotes(&content, context);
+    let escape_text = context.options.escape_text;
+    let finish_markup = |text: &str| {
+        if escape_text {
+            restore_markup(&escape::escape_markup(text))
+        } else {
+            restore_markup(text)
+        }
+    };
+    let content = finish_markup(&content);
+    for (_, text) in &mut context.inline_footnotes[queued_footnotes..] {
+        *text = finish_markup(text);
+    }
+
+    let mut nesting = std::mem::take(&mut context.nesting);
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut i = 0;
+    let first_block = context.blocks.len();
+    let blocks = &mut context.blocks;
+    // Where the lines of paragraph text that can be wrapped start in `result`.
+    let mut wrappable = HashSet::new();
+
+    while let Some(&line) = lines.get(i) {
+        i += 1;
+        if let Some(caps) = FOOTNOTE_DEFINITION_REGEX.captures(line) {
+            nesting.reset();
+            blocks.push(Block::new(BlockKind::Footnote, &caps[1]));
+            result.push_str(&format!("^ {}\n", &caps[1]));
+            if !caps[2].is_empty() {
+                result.push_str(&format!("{}\n", &caps[2]));
+            }
+        } else if let Some((definition, consumed)) = convert_definition(&lines[i - 1..]) {
+            nesting.reset();
+            blocks.push(Block::new(BlockKind::Definition, line.trim()));
+            result.push_str(&definition);
+            i += consumed - 1;
+        } else if let Some((table, consumed)) =
+            table::convert(&lines[i - 1..]).filter(|_| passes.tables)
+        {
+            context.stats.tables += 1;
+            nesting.reset();
+            blocks.push(Block::new(BlockKind::Table { rows: consumed }, line.trim()));
+            result.push_str(&table);
+            i += consumed - 1;
+        } else if let Some(level) = lines
+            .get(i)
+            .and_then(|next| setext_level(line, next))
+            .filter(|_| passes.headings)
+        {
+            context.stats.headings += 1;
+            nesting.reset();
+            let text = without_attributes(line.trim());
+            blocks.push(Block::new(BlockKind::Heading { level }, text));
+            result.push_str(&format!("{} {}\n", "*".repeat(level), text));
+            i += 1;
+        } else if RULE_REGEX.is_match(line) {
+            nesting.reset();
+            blocks.push(Block::new(BlockKind::Rule, ""));
+            result.push_str("___\n");
+        } else if let Some(caps) = HEADING_REGEX.captures(line).filter(|_| passes.headings) {
+            context.stats.headings += 1;
+            nesting.reset();
+            let level = caps[1].len().min(MAX_HEADING_LEVEL);
+            let text = heading_text(caps.get(2).unwrap().as_str());
+            blocks.push(Block::new(BlockKind::Heading { level }, text));
+            result.push_str(&format!("{} {}\n", "*".repeat(level), text));
+        } else if let Some(caps) = QUOTE_REGEX.captures(line) {
+            nesting.reset();
+            let marker = ">".repeat(caps[1].matches('>').count());
+            let callout = CALLOUT_REGEX
+                .captures(&caps[2])
+                .map(|callout| callout_label(&callout));
+            let text = callout.as_deref().unwrap_or(&caps[2]);
+            let depth = marker.len();
+            blocks.push(Block::new(BlockKind::Quote { depth }, text));
+            if text.is_empty() {
+                result.push_str(&format!("{}\n", marker));
+            } else {
+                result.push_str(&format!("{} {}\n", marker, text));
+            }
+        } else if let Some((caps, status)) = TASK_REGEX
+            .captures(line)
+            .filter(|_| passes.todos)
+            .and_then(|caps| {
+                let status = context.options.todo_status(caps[2].chars().next()?)?;
+                Some((caps, status))
+            })
+        {
+            context.stats.tasks += 1;
+            let depth = nesting.depth(&caps[1]);
+            let marker = "-".repeat(depth);
+            let (text, extensions) = if context.options.task_metadata {
+                task_metadata(&caps[3])
+            } else {
+                (caps[3].to_string(), String::new())
+            };
+            blocks.push(Block::new(BlockKind::Task { depth }, &text));
+            result.push_str(&format!("{} ({}{}) {}\n", marker, status, extensions, text));
+        } else if let Some(caps) = BULLET_REGEX.captures(line).filter(|_| passes.lists) {
+            context.stats.list_items += 1;
+            let depth = nesting.depth(&caps[1]);
+            let marker = "-".repeat(depth);
+            let text = &caps[2];
+            let ordered = false;
+            blocks.push(Block::new(BlockKind::ListItem { depth, ordered }, text));
+            result.push_str(&format!("{} {}\n", marker, text));
+        } else if let Some(caps) = ORDERED_REGEX.captures(line).filter(|_| passes.lists) {
+            // Neorg numbers `~` items itself, so the Markdown numbers are
+            // dropped. As when Markdown is rendered, `1. 3. 5.` comes out as
+            // 1, 2, 3, and a list restarts after anything that ends it.
+            context.stats.list_items += 1;
+            let depth = nesting.depth(&caps[1]);
+            let marker = "~".repeat(depth);
+            let text = &caps[2];
+            let ordered = true;
+            blocks.push(Block::new(BlockKind::ListItem { depth, ordered }, text));
+            result.push_str(&format!("{} {}\n", marker, text));
+        } else if let Some(indent) = nesting
+            .continuation_indent()
+            .filter(|_| line.starts_with([' ', '\t']) && !line.trim().is_empty())
+        {
+            // Text indented under a list item belongs to it, so is lined up
+            // with the Neorg item's text and keeps the list going.
+            blocks.push(Block::new(BlockKind::ListContinuation, line.trim()));
+            result.push_str(&format!("{}{}\n", " ".repeat(indent), line.trim()));
+        } else {
+            if !line.trim().is_empty() {
+                nesting.reset();
+                blocks.push(Block::new(BlockKind::Text, line.trim()));
+                if is_paragraph_text(line) {
+                    wrappable.insert(result.len());
+                }
+            }
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+
+    context.nesting = nesting;
+    if let Some(width) = context.options.wrap {
+        result = wrap_paragraphs(&result, &wrappable, width, &code_spans);
+    }
+    for block in &mut context.blocks[first_block..] {
+        block.text = restore_inline_code(&block.text, &code_spans);
+    }
+
+    for (_, text) in &mut context.inline_footnotes[queued_footnotes..] {
+        *text = restore_inline_code(text, &code_spans);
+    }
+    if context.options.html {
+        result = convert_html(&result);
+    }
+    restore_inline_code(&result, &code_spans)
+}
+
+/// Whether `line`, left as it is by the conversions, is ordinary paragraph
+/// text, rather than Markdown whose conversion was turned off, such as a list
+/// item, or a table row.
+fn is_paragraph_text(line: &str) -> bool {
+    let line = line.trim_start();
+    ![&*HEADING_REGEX, &BULLET_REGEX, &ORDERED_REGEX, &QUOTE_REGEX]
+        .iter()
+        .any(|re| re.is_match(line))
+        && !line.starts_with(['|', ':'])
+}
+
+/// Reflows the runs of paragraph text in `content`, the lines starting at the
+/// `wrappable` offsets, to lines of at most `width` characters where the
+/// words allow. A hard line break is kept, and links and inline code, still
+/// in the `spans` they're protected in, are never split.
+fn wrap_paragraphs(
+    content: &str,
+    wrappable: &HashSet<usize>,
+    width: usize,
+    spans: &[String],
+) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if wrappable.contains(&start) {
+            let line = line.trim_end_matches('\n');
+            paragraph.push(line);
+            let backslashes = line.len() - line.trim_end_matches('\\').len();
+            if backslashes % 2 == 1 {
+                result.push_str(&wrap_lines(&paragraph, width, spans));
+                paragraph.clear();
+            }
+            continue;
+        }
+        result.push_str(&wrap_lines(&paragraph, width, spans));
+        paragraph.clear();
+        result.push_str(line);
+    }
+    result.push_str(&wrap_lines(&paragraph, width, spans));
+    result
+}
+
+/// Fills lines of at most `width` characters with the words of `lines`,
+/// indented as the first of them is.
+fn wrap_lines(lines: &[&str], width: usize, spans: &[String]) -> String {
+    let Some(first) = lines.first() else {
+        return String::new();
+    };
+    let indent = &first[..first.len() - first.trim_start().len()];
+    let mut result = String::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in lines
+        .iter()
+        .flat_map(|line| WRAP_UNIT_REGEX.find_iter(line))
+        .map(|m| m.as_str())
+    {
+        let word_width = restore_inline_code(word, spans).chars().count();
+        if line.is_empty() {
+            line.push_str(indent);
+            line_width = indent.chars().count();
+        } else if line_width + 1 + word_width <= width || !can_start_line(word) {
+            line.push(' ');
+            line_width += 1;
+        } else {
+            result.push_str(&line);
+            result.push('\n');
+            line = indent.to_string();
+            line_width = indent.chars().count();
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    result.push_str(&line);
+    result.push('\n');
+    result
+}
+
+/// Whether `word` can start a wrapped line without Neorg reading it as
+/// markup there, as it would `-` as a list item, or `@code` as a tag.
+fn can_start_line(word: &str) -> bool {
+    !word.starts_with(['@', '#', '+', '.', '|']) && !word.chars().all(|c| "*-~>^$:%=_".contains(c))
+}
+
+/// Converts inline links, `[text](url "title")`, and images, `![alt](url)`.
+/// These are parsed by hand, so that brackets in the text and parentheses in
+/// the URL, as in Wikipedia's `Mercury_(planet)`, can be balanced. Titles have
+/// no Neorg equivalent, so are dropped.
+fn convert_inline_links(content: &str, stats: &mut Stats) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find('[') {
+        let image = rest[..start].ends_with('!');
+        let Some((text, url, len)) = parse_inline_link(&rest[start..])
+            .filter(|(text, _, _)| image || !text.trim().is_empty())
+        else {
+            result.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+        stats.links += 1;
+        if image {
+            result.push_str(&rest[..start - 1]);
+            let (alt, size) = split_image_size(text);
+            let description = image_description(alt, size);
+            result.push_str(&format!("{{image:{}}}[{}]", url, description));
+        } else {
+            result.push_str(&rest[..start]);
+            // Link text can itself be an image, as in a linked badge. Any
+            // other brackets in it would end the Neorg description early.
+            let inner = convert_inline_links(text, stats);
+            let description = if inner != text {
+                inner
+            } else {
+                text.replace('[', "\\[").replace(']', "\\]")
+            };
+            result.push_str(&format!("{{{}}}[{}]", url, description));
+        }
+        rest = &rest[start + len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Splits an Obsidian `|200` or `|200x100` size off the end of an image's alt
+/// text.
+fn split_image_size(alt: &str) -> (&str, Option<&str>) {
+    match alt.rsplit_once('|') {
+        Some((alt, size)) if IMAGE_SIZE_REGEX.is_match(size.trim()) => (alt, Some(size.trim())),
+        _ => (alt, None),
+    }
+}
+
+/// The description of an image link. Neorg can't size images, so any size is
+/// kept in the description rather than lost.
+fn image_description(alt: &str, size: Option<&str>) -> String {
+    match size {
+        None => alt.to_string(),
+        Some(size) if alt.trim().is_empty() => format!("{}px", size),
+        Some(size) => format!("{} ({}px)", alt.trim_end(), size),
+    }
+}
+
+/// Parses the `[text](destination "title")` at the start of `s`, returning
+/// the text, the destination and the length of the link.
+fn parse_inline_link(s: &str) -> Option<(&str, &str, usize)> {
+    let text_end = closing_bracket(s, '[', ']')?;
+    let target = &s[text_end + 1..];
+    if !target.starts_with('(') {
+        return None;
+    }
+    let target_end = closing_bracket(target, '(', ')')?;
+    let target_text = target[1..target_end].trim();
+    // Obsidian allows spaces in a destination, so everything but a quoted
+    // title is taken as the URL.
+    let url = match target_text.strip_prefix('<') {
+        Some(bracketed) => bracketed.split_once('>')?.0,
+        None => LINK_TITLE_REGEX
+            .captures(target_text)
+            .map_or(target_text, |caps| caps.get(1).unwrap().as_str()),
+    };
+    if url.is_empty() || url.contains('\n') {
+        return None;
+    }
+    Some((&s[1..text_end], url, text_end + 1 + target_end + 1))
+}
+
+/// The index of the `close` bracket matching the `open` one `s` starts with,
+/// if there is one before the end of the paragraph.
+fn closing_bracket(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut previous = None;
+    for (i, c) in s.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else if c == '\n' && previous == Some('\n') {
+            return None;
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+/// Rewrites converted links to other Markdown notes as Neorg file links to
+/// their conversion. An anchor becomes a link to any linkable of that name,
+/// as with Obsidian heading links.
+fn convert_note_links(content: &str) -> String {
+    NOTE_LINK_REGEX
+        .replace_all(content, |caps: &regex::Captures| match caps.get(2) {
+            Some(anchor) => format!("{{:{}.norg:# {}}}", &caps[1], anchor.as_str()),
+            None => format!("{{:{}.norg:}}", &caps[1]),
+        })
+        .to_string()
+}
+
+/// Drops Obsidian `^block-id`s, which Neorg has no way to link to. An ID on a
+/// line of its own goes with the blank line before it, so the blocks around it
+/// stay a single blank line apart.
+fn strip_block_ids(content: &str) -> String {
+    let mut result: Vec<&str> = Vec::new();
+    for line in content.split('\n') {
+        match BLOCK_ID_REGEX.find(line) {
+            Some(id) if id.start() == 0 => {
+                if result.last().is_some_and(|last| last.trim().is_empty()) {
+                    result.pop();
+                }
+            }
+            Some(id) => result.push(&line[..id.start()]),
+            None => result.push(line),
+        }
+    }
+    result.join("\n")
+}
+
+/// Replaces the typographic `PUNCTUATION` in `content` with plain ASCII.
+fn normalize_punctuation(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    for c in content.chars() {
+        match PUNCTUATION.iter().find(|(from, _)| *from == c) {
+            Some((_, to)) => result.push_str(to),
+            None => result.push(c),
+        }
+    }
+    result
+}
+
+/// Marks the hard line breaks in paragraphs, lines ending in two or more
+/// spaces or a backslash, with Neorg's trailing backslash. A break at the end
+/// of a block is meaningless, so is dropped.
+fn convert_hard_breaks(content: &str) -> String {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut result = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_end_matches(' ');
+        let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+        let text = if line.len() - trimmed.len() >= 2 {
+            trimmed
+        } else if backslashes % 2 == 1 {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            result.push(line.to_string());
+            continue;
+        };
+
+        let continues = lines.get(i + 1).is_some_and(|next| {
+            !next.trim().is_empty()
+                && !HEADING_REGEX.is_match(next)
+                && !BULLET_REGEX.is_match(next)
+                && !ORDERED_REGEX.is_match(next)
+                && !QUOTE_REGEX.is_match(next)
+                && !RULE_REGEX.is_match(next)
+        });
+        // A table row can't have a line break, and it would hide the row's
+        // closing pipe.
+        let text = text.trim_end();
+        if continues && !text.is_empty() && !text.ends_with('|') {
+            result.push(format!("{}\\", text));
+        } else {
+            result.push(text.to_string());
+        }
+    }
+    result.join("\n")
+}
+
+/// The bold label that a callout's or alert's first line becomes: its type,
+/// made readable, and its title if it has one. Neorg has neither, so they're
+/// kept as quotes.
+fn callout_label(callout: &regex::Captures) -> String {
+    let kind = callout[1].to_lowercase();
+    let label = match ALERT_LABELS
+        .iter()
+        .chain(CALLOUT_LABELS)
+        .find(|(name, _)| *name == kind)
+    {
+        Some((_, label)) => label.to_string(),
+        None => {
+            let mut chars = kind.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    };
+    match callout[2].trim() {
+        "" => format!("*{}*", label),
+        title => format!("*{}: {}*", label, title),
+    }
+}
+
+/// Converts the definition list entry starting at the first of `lines`, a
+/// term followed by one or more `: definition` lines, if there is one. Returns
+/// the Neorg definition and the number of lines it was made from.
+///
+/// A single definition becomes a single-paragraph Neorg definition, while
+/// several become paragraphs of a ranged one.
+fn convert_definition(lines: &[&str]) -> Option<(String, usize)> {
+    let (term, rest) = lines.split_first()?;
+    let term = term.trim();
+    if term.is_empty()
+        || DEFINITION_REGEX.is_match(term)
+        || [&*HEADING_REGEX, &BULLET_REGEX, &ORDERED_REGEX, &QUOTE_REGEX]
+            .iter()
+            .any(|re| re.is_match(term))
+    {
+        return None;
+    }
+
+    let definitions: Vec<&str> = rest
+        .iter()
+        .map_while(|line| DEFINITION_REGEX.captures(line))
+        .map(|caps| caps.get(1).unwrap().as_str().trim_end())
+        .collect();
+    let definition = match definitions.as_slice() {
+        [] => return None,
+        [definition] => format!("$ {}\n{}\n", term, definition),
+        definitions => format!("$$ {}\n{}\n$$\n", term, definitions.join("\n\n")),
+    };
+    Some((definition, definitions.len() + 1))
+}
+
+/// Converts Obsidian `[[...]]` links and `![[...]]` embeds. Links to a heading
+/// use Neorg's `#` "any linkable" target, since the heading's level isn't
+/// known from the link.
+///
+/// Neorg has no transclusion, so embedded images become image links, embedded
+/// notes become ordinary links and any other embedded file a plain file link.
+///
+/// An embedded image's alias can give its size, as `|200` or `|alt|200x100`,
+/// which is kept in its description.
+///
+/// Neorg can't link to an arbitrary block either, so links to a `^block-id`
+/// point at the block's note, and within the same note are reduced to their
+/// alias.
+fn convert_wikilinks(content: &str, stats: &mut Stats) -> String {
+    WIKILINK_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let embed = !caps[1].is_empty();
+            let page = caps[2].trim();
+            let heading = caps.get(3).map(|m| m.as_str().trim());
+            let block = heading.is_some_and(|heading| heading.starts_with('^'));
+            let heading = heading.filter(|_| !block);
+            let extension = page
+                .rsplit_once('.')
+                .map(|(_, ext)| ext.to_lowercase())
+                .filter(|ext| ext.chars().all(|c| c.is_ascii_alphabetic()));
+            let image = embed
+                && extension
+                    .as_deref()
+                    .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext));
+            let file = embed && !image && extension.is_some_and(|ext| ext != "md");
+
+            let mut link = match (page, heading) {
+                ("", None) if block => match caps.get(4) {
+                    Some(alias) => return alias.as_str().to_string(),
+                    None => return caps[0].to_string(),
+                },
+                ("", None) => return caps[0].to_string(),
+                ("", Some(heading)) => format!("{{# {}}}", heading),
+                (page, _) if image => format!("{{image:{}}}", page),
+                (page, _) if file => format!("{{/ {}}}", page),
+                (page, Some(heading)) => format!("{{:{}.norg:# {}}}", page, heading),
+                (page, None) => format!("{{:{}.norg:}}", page),
+            };
+            if let Some(alias) = caps.get(4).map(|m| m.as_str()) {
+                let description = if !image {
+                    alias.to_string()
+                } else if IMAGE_SIZE_REGEX.is_match(alias.trim()) {
+                    image_description("", Some(alias.trim()))
+                } else {
+                    let (alt, size) = split_image_size(alias);
+                    image_description(alt, size)
+                };
+                link.push_str(&format!("[{}]", description));
+            }
+            stats.links += 1;
+            link
+        })
+        .to_string()
+}
+
+/// Converts footnote references to links to their Neorg footnote, leaving
+/// references to undefined footnotes as they are. Inline `^[...]` footnotes
+/// are numbered after the document's own and queued to be defined at the end.
+fn convert_footnotes(content: &str, context: &mut Context) -> String {
+    let content = FOOTNOTE_REFERENCE_REGEX.replace_all(content, |caps: &regex::Captures| {
+        let label = &caps[2];
+        let definition = caps.get(1).is_some() && !caps[3].is_empty();
+        if definition || !context.footnotes.contains(label) {
+            return caps[0].to_string();
+        }
+        format!(
+            "{}{{^ {}}}{}",
+            caps.get(1).map_or("", |m| m.as_str()),
+            label,
+            &caps[3]
+        )
+    });
+    INLINE_FOOTNOTE_REGEX
+        .replace_all(&content, |caps: &regex::Captures| {
+            let label = (1..)
+                .map(|n: usize| n.to_string())
+                .find(|label| {
+                    !context.footnotes.contains(label)
+                        && !context.inline_footnotes.iter().any(|(l, _)| l == label)
+                })
+                .unwrap();
+            context
+                .inline_footnotes
+                .push((label.clone(), caps[1].to_string()));
+            format!("{{^ {}}}", label)
+        })
+        .to_string()
+}
+
+/// Collects Obsidian `#tags` into `tags`, for the document's metadata. Neorg
+/// reads a `#` at the start of a line as a tag of its own, so tags there are
+/// escaped; elsewhere they're left in place as plain text.
+fn collect_tags(content: &str, tags: &mut Vec<String>) -> String {
+    TAG_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let tag = &caps[2];
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+            if caps[1].is_empty() {
+                format!("\\#{}", tag)
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string()
+}
+
+/// The heading level of `line` if it's the text of a setext heading, i.e. it's
+/// underlined by a `next` line of `=` (level 1) or `-` (level 2). A `---` line
+/// after anything other than paragraph text is a horizontal rule instead.
+fn setext_level(line: &str, next: &str) -> Option<usize> {
+    let underline = SETEXT_UNDERLINE_REGEX.captures(next)?;
+    let is_paragraph_text = !line.trim().is_empty()
+        && !line.starts_with("    ")
+        && ![
+            &*HEADING_REGEX,
+            &*RULE_REGEX,
+            &*QUOTE_REGEX,
+            &*BULLET_REGEX,
+            &*ORDERED_REGEX,
+        ]
+        .iter()
+        .any(|re| re.is_match(line));
+    if !is_paragraph_text {
+        return None;
+    }
+    Some(if underline[1].starts_with('=') { 1 } else { 2 })
+}
+
+/// Moves the Obsidian Tasks plugin's dates and priority out of a task's text,
+/// returning the rest of the text and the equivalent Neorg TODO extensions: a
+/// due date (`📅`) becomes `< date`, a start date (`🛫`) `> date`, scheduled
+/// (`⏳`) and done (`✅`) dates a `@ date` timestamp, and the priorities from
+/// highest (`🔺`) to lowest (`⏬`) `# A` to `# E`.
+fn task_metadata(text: &str) -> (String, String) {
+    let mut extensions = String::new();
+    let text = TASK_METADATA_REGEX.replace_all(text, |caps: &regex::Captures| {
+        let extension = match (caps.get(1), caps.get(3)) {
+            (Some(date), _) => {
+                let kind = match date.as_str() {
+                    "📅" | "🗓" => '<',
+                    "🛫" => '>',
+                    _ => '@',
+                };
+                format!("{} {}", kind, &caps[2])
+            }
+            (_, Some(priority)) => {
+                let level = match priority.as_str() {
+                    "🔺" => 'A',
+                    "⏫" => 'B',
+                    "🔼" => 'C',
+                    "🔽" => 'D',
+                    _ => 'E',
+                };
+                format!("# {}", level)
+            }
+            _ => unreachable!(),
+        };
+        extensions.push('|');
+        extensions.push_str(&extension);
+        ""
+    });
+    (text.trim_end().to_string(), extensions)
+}
+
+/// Converts `~subscript~` and `^superscript^` to Neorg's `,subscript,` and
+/// `^superscript^`. Neorg only recognises markup at the edges of words, so
+/// within a word, as in `H~2~O`, it's joined on with the `:` link modifier.
+fn convert_scripts(content: &str) -> String {
+    let content = convert_script(&SUBSCRIPT_REGEX, content, '\u{7}');
+    convert_script(&SUPERSCRIPT_REGEX, &content, '\u{8}')
+}
+
+fn convert_script(re: &Regex, content: &str, marker: char) -> String {
+    re.replace_all(content, |caps: &regex::Captures| {
+        let before = &caps[1];
+        let after = &content[caps.get(0).unwrap().end()..];
+        let in_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+        let open = if in_word(before.chars().last()) {
+            ":"
+        } else {
+            ""
+        };
+        let close = if in_word(after.chars().next()) {
+            ":"
+        } else {
+            ""
+        };
+        format!(
+            "{}{}{}{}{}{}",
+            before, open, marker, &caps[2], marker, close
+        )
+    })
+    .to_string()
+}
+
+/// Replaces the placeholders left by the inline conversions with the markup
+/// they stand for.
+fn restore_markup(content: &str) -> String {
+    content
+        .chars()
+        .map(|c| {
+            MARKUP_PLACEHOLDERS
+                .iter()
+                .find(|(placeholder, _)| *placeholder == c)
+                .map_or(c, |(_, markup)| *markup)
+        })
+        .collect()
+}
+
+/// Tracks the indentation of the list items enclosing the current one, so that
+/// an item's nesting depth can be worked out from its leading whitespace.
+/// Neorg expresses that depth by repeating the list marker instead.
+#[derive(Default)]
+struct ListNesting {
+    indents: Vec<usize>,
+}
+
+impl ListNesting {
+    /// Returns the 1-based depth of a list item with the given indentation.
+    fn depth(&mut self, indent: &str) -> usize {
+        let width = indent_width(indent);
+        while self.indents.last().is_some_and(|&w| w > width) {
+            self.indents.pop();
+        }
+        if self.indents.last() != Some(&width) {
+            self.indents.push(width);
+        }
+        self.indents.len()
+    }
+
+    /// The indentation that lines up with the text of the last list item,
+    /// if there's a list going.
+    fn continuation_indent(&self) -> Option<usize> {
+        match self.indents.len() {
+            0 => None,
+            depth => Some(depth + 1),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.indents.clear();
+    }
+}
+
+/// Width of leading whitespace in columns, with tabs counting as four.
+fn indent_width(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+/// Takes up to `width` columns of leading whitespace off `line`.
+fn strip_indent(line: &str, width: usize) -> &str {
+    let mut columns = 0;
+    let start = line
+        .char_indices()
+        .find(|&(_, c)| {
+            columns += match c {
+                ' ' => 1,
+                '\t' => 4,
+                _ => return true,
+            };
+            columns > width
+        })
+        .map_or(line.len(), |(i, _)| i);
+    &line[start..]
+}
+
+/// Swaps every inline code span for a numbered placeholder so that none of the
+/// other conversions can touch its contents. Neorg uses backticks for inline
+/// verbatim too, so the spans are restored as-is by `restore_inline_code`.
+fn protect_inline_code(content: &str) -> (String, Vec<String>) {
+    let mut spans = Vec::new();
+    let protected = CODE_SPAN_REGEX.replace_all(content, |caps: &regex::Captures| {
+        spans.push(caps[0].to_string());
+        format!("\u{2}{}\u{3}", spans.len() - 1)
+    });
+    (protected.to_string(), spans)
+}
+
+/// Converts the raw HTML tags with a Neorg equivalent, and strips the tags of
+/// other HTML elements, keeping their content. Runs after the line-by-line
+/// conversion, so a `<br>` can't turn the text after it into a new block.
+fn convert_html(content: &str) -> String {
+    let content = convert_html_breaks(content);
+    HTML_TAG_REGEX
+        .replace_all(&content, |caps: &regex::Captures| {
+            match caps[1].to_lowercase().as_str() {
+                "b" | "strong" => "*",
+                "i" | "em" => "/",
+                "u" => "_",
+                name if HTML_ELEMENTS.contains(&name) => "",
+                _ => &caps[0],
+            }
+            .to_string()
+        })
+        .to_string()
+}
+
+/// Turns each `<br>` into a hard line break, carrying on the list item or
+/// quote it's in on the next line. A heading or table row can't break, so it
+/// gets a space instead, and a break at the end of a block is dropped.
+fn convert_html_breaks(content: &str) -> String {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut in_table = false;
+    let mut result = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match *line {
+            "@table" => in_table = true,
+            "@end" => in_table = false,
+            _ => {}
+        }
+        if !HTML_BREAK_REGEX.is_match(line) {
+            result.push(line.to_string());
+            continue;
+        }
+        if in_table || NEORG_HEADING_REGEX.is_match(line) {
+            let text = HTML_BREAK_REGEX.replace_all(line, " ");
+            result.push(text.trim_end().to_string());
+            continue;
+        }
+
+        let prefix = continuation_prefix(line);
+        let line_break = format!("\\\n{}", prefix);
+        let text = HTML_BREAK_REGEX.replace_all(line, NoExpand(&line_break));
+        // A break ending the line holds only if the next line carries on.
+        let text = match text.strip_suffix(&line_break) {
+            Some(text) => {
+                let continues = lines.get(i + 1).is_some_and(|next| {
+                    next.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                        !rest.trim().is_empty()
+                            && !rest.starts_with(char::is_whitespace)
+                            && !NEORG_BLOCK_START_REGEX.is_match(rest)
+                    })
+                });
+                if continues {
+                    format!("{}\\", text)
+                } else {
+                    text.to_string()
+                }
+            }
+            None => text.to_string(),
+        };
+        result.push(text);
+    }
+    result.join("\n")
+}
+
+/// What a converted `line` carrying on onto the next starts that line with:
+/// the indentation that lines up with a list item's text, a quote's markers,
+/// or the line's own indentation.
+fn continuation_prefix(line: &str) -> String {
+    match NEORG_ITEM_REGEX.captures(line) {
+        Some(caps) => match caps.get(1) {
+            Some(quote) => format!("{} ", quote.as_str()),
+            None => " ".repeat(caps[0].len()),
+        },
+        None => line[..line.len() - line.trim_start().len()].to_string(),
+    }
+}
+
+/// Protects inline math from conversion, as code spans are, converting
+/// `$$display$$` math within a paragraph to inline math too. A `$` is only
+/// taken as math when it has a matching closing `$`, so prices are left alone.
+fn protect_inline_math(content: &str, spans: &mut Vec<String>) -> String {
+    INLINE_MATH_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            if !caps[1].is_empty() || !caps[3].is_empty() {
+                return caps[0].to_string();
+            }
+            spans.push(format!("${}$", &caps[2]));
+            format!("\u{2}{}\u{3}", spans.len() - 1)
+        })
+        .to_string()
+}
+
+/// Protects backslash escaped characters from conversion, as code spans are.
+/// The escape is dropped unless the character would be Neorg markup, as
+/// Neorg escapes characters with a backslash too.
+fn protect_escapes(content: &str, spans: &mut Vec<String>) -> String {
+    ESCAPE_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let indent = caps.get(1).map(|m| m.as_str());
+            let c = caps[2].chars().next().unwrap();
+            let special = NEORG_INLINE_SPECIAL.contains(c)
+                || (indent.is_some() && NEORG_LINE_START_SPECIAL.contains(c));
+            spans.push(if special {
+                format!("\\{}", c)
+            } else {
+                c.to_string()
+            });
+            format!("{}\u{2}{}\u{3}", indent.unwrap_or(""), spans.len() - 1)
+        })
+        .to_string()
+}
+
+/// Decodes HTML character references, `&amp;`, `&#169;` or `&#xA9;`, to the
+/// characters they stand for. A character Neorg would read as markup, which
+/// the reference was likely used to avoid, is escaped and protected from
+/// conversion as an escaped one is. Unknown entities are left as they are.
+fn decode_entities(content: &str, spans: &mut Vec<String>) -> String {
+    ENTITY_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let code = match (caps.get(2), caps.get(3)) {
+                (Some(decimal), _) => decimal.as_str().parse().ok(),
+                (_, Some(hex)) => u32::from_str_radix(hex.as_str(), 16).ok(),
+                _ => None,
+            };
+            let c = match code {
+                Some(code) => char::from_u32(code).filter(|&c| c != '\0'),
+                None => HTML_ENTITIES
+                    .iter()
+                    .find(|(name, _)| *name == &caps[4])
+                    .map(|(_, c)| *c),
+            };
+            let Some(c) = c else {
+                return caps[0].to_string();
+            };
+            let indent = caps.get(1).map(|m| m.as_str());
+            let special = NEORG_INLINE_SPECIAL.contains(c)
+                || (indent.is_some() && NEORG_LINE_START_SPECIAL.contains(c));
+            if !special {
+                return format!("{}{}", indent.unwrap_or(""), c);
+            }
+            spans.push(format!("\\{}", c));
+            format!("{}\u{2}{}\u{3}", indent.unwrap_or(""), spans.len() - 1)
+        })
+        .to_string()
+}
+
+/// Protects the targets of the converted links from the inline conversions
+/// that follow, as code spans are, so that only their descriptions are styled:
+/// `[**bold**](a_b_c)` becomes `{a_b_c}[*bold*]`.
+fn protect_link_targets(content: &str, spans: &mut Vec<String>) -> String {
+    LINK_TARGET_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            let is_link = caps.get(2).is_some()
+                || LINK_TARGET_PREFIXES
+                    .iter()
+                    .any(|prefix| caps[1].starts_with(prefix));
+            if !is_link {
+                return caps[0].to_string();
+            }
+            spans.push(format!("{{{}}}", &caps[1]));
+            format!(
+                "\u{2}{}\u{3}{}",
+                spans.len() - 1,
+                caps.get(2).map_or("", |m| m.as_str())
+            )
+        })
+        .to_string()
+}
+
+/// Converts single-line HTML comments to Neorg inline comments, protected
+/// from further conversion like code spans are.
+fn convert_html_comments(content: &str, spans: &mut Vec<String>) -> String {
+    HTML_COMMENT_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            if caps[1].is_empty() {
+                return String::new();
+            }
+            spans.push(format!("%{}%", &caps[1]));
+            format!("\u{2}{}\u{3}", spans.len() - 1)
+        })
+        .to_string()
+}
+
+fn restore_inline_code(content: &str, spans: &[String]) -> String {
+    PLACEHOLDER_REGEX
+        .replace_all(content, |caps: &regex::Captures| {
+            spans[caps[1].parse::<usize>().unwrap()].clone()
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_convert_headings() -> Result<()> {
+        let markdown = "# Heading 1\n## Heading 2\n### Heading 3";
+        let expected = "* Heading 1\n** Heading 2\n*** Heading 3";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_closing_hashes() -> Result<()> {
+        let markdown = "## Title ##\n### Issue #42 ###\n# C#\n#\n## ##";
+        let expected = "** Title\n*** Issue #42\n* C#\n* \n** ";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_needs_space_after_hashes() -> Result<()> {
+        let options = ConvertOptions {
+            tags: false,
+            ..Default::default()
+        };
+        assert_eq!(convert_with_options("#Title", &options)?, "#Title");
+        assert_eq!(convert_with_options("#\tTitle", &options)?, "* Title");
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_setext_headings() -> Result<()> {
+        let markdown = "Title\n=====\n\nSubtitle\n---\n\nText\n\n---\n\n- Item\n---";
+        let expected = "* Title\n\n** Subtitle\n\nText\n\n___\n\n- Item\n___";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_code_blocks() -> Result<()> {
+        let markdown = "```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```";
+        let expected = "@code rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_lists() -> Result<()> {
+        let markdown = "- Item 1\n- Item 2\n  - Subitem 2.1\n- Item 3";
+        let expected = "- Item 1\n- Item 2\n-- Subitem 2.1\n- Item 3";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_nested_lists_four_space_indent() -> Result<()> {
+        let markdown = "- One\n    - Two\n        - Three\n    - Two again\n- One again";
+        let expected = "- One\n-- Two\n--- Three\n-- Two again\n- One again";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_nested_lists_tab_indent() -> Result<()> {
+        let markdown = "- One\n\t- Two\n\t\t- [ ] Three\n- One again";
+        let expected = "- One\n-- Two\n--- ( ) Three\n- One again";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_ordered_lists() -> Result<()> {
+        let markdown = "1. First\n2. Second\n3. Third";
+        let expected = "~ First\n~ Second\n~ Third";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_ordered_lists_under_bullets() -> Result<()> {
+        let markdown = "- Groceries\n  1. Milk\n  2. Eggs\n     - [ ] Free range\n     - Brown\n       1. Dozen\n- Chores\n    1. Laundry";
+        let expected = "- Groceries\n~~ Milk\n~~ Eggs\n--- ( ) Free range\n--- Brown\n~~~~ Dozen\n- Chores\n~~ Laundry";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_continuation_lines() -> Result<()> {
+        let markdown = "- A long item\n  that wraps\n  - Nested item\n    that wraps too\n\n    and continues\n- Back out\n\nText";
+        let expected = "- A long item\n  that wraps\n-- Nested item\n   that wraps too\n\n   and continues\n- Back out\n\nText";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ordered_list_numbering() -> Result<()> {
+        let markdown = "1. One\n3. Three\n5. Five\n\nA paragraph\n\n1) Restarted\n1) Again";
+        let expected = "~ One\n~ Three\n~ Five\n\nA paragraph\n\n~ Restarted\n~ Again";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ordered_list_restarts_after_other_content() -> Result<()> {
+        let markdown = "1. First\n   1. Nested\n\n```sh\nls\n```\n\n2. Second\n   2. Nested again";
+        let expected = "~ First\n~~ Nested\n\n@code bash\nls\n@end\n\n~ Second\n~~ Nested again";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_mixed_ordered_and_unordered_lists() -> Result<()> {
+        let markdown = "1. First\n   - Detail\n   - More detail\n2. Second\n   1. Step one";
+        let expected = "~ First\n-- Detail\n-- More detail\n~ Second\n~~ Step one";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_blockquote() -> Result<()> {
+        let markdown = "> A wise quote\n>with *style*";
+        let expected = "> A wise quote\n> with /style/";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_multi_paragraph_blockquote() -> Result<()> {
+        let markdown = "> First paragraph\n>\n> Second paragraph";
+        let expected = "> First paragraph\n>\n> Second paragraph";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_nested_blockquote() -> Result<()> {
+        let markdown = "> Outer\n>> Inner\n> > Also inner";
+        let expected = "> Outer\n>> Inner\n>> Also inner";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_table() -> Result<()> {
+        let markdown = "Before\n\n| Name | Value |\n| :--- | ---: |\n| a | 1 |\n| **b** | 2 |\n| c | 3 |\n\nAfter | with a pipe";
+        let expected =
+            "Before\n\n@table\nName | Value\n-\na | 1\n*b* | 2\nc | 3\n@end\n\nAfter | with a pipe";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_horizontal_rules() -> Result<()> {
+        let markdown = "One\n\n---\n\nTwo\n\n***\n\nThree\n\n___\n\nFour\n\n- - -\n\n* * * *";
+        let expected = "One\n\n___\n\nTwo\n\n___\n\nThree\n\n___\n\nFour\n\n___\n\n___";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_todos() -> Result<()> {
+        let markdown = "- [ ] Todo item\n- [x] Completed item";
+        let expected = "- ( ) Todo item\n- (x) Completed item";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_todo_statuses() -> Result<()> {
+        let markdown = "- [X] Done\n- [/] In progress\n- [-] Cancelled\n- [?] Unsure\n- [!] Important\n- [=] On hold\n- [+] Recurring\n* [ ] Star bullet\n- [~] Unknown";
+        let expected = "- (x) Done\n- (-) In progress\n- (_) Cancelled\n- (?) Unsure\n- (!) Important\n- (=) On hold\n- (+) Recurring\n- ( ) Star bullet\n- [~] Unknown";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_custom_todo_statuses() -> Result<()> {
+        let options = ConvertOptions {
+            todo_statuses: vec![(' ', ' '), ('>', '=')],
+            ..Default::default()
+        };
+        let markdown = "- [>] Deferred\n- [x] Not mapped";
+        let expected = "- (=) Deferred\n- [x] Not mapped";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_task_metadata() -> Result<()> {
+        let options = ConvertOptions {
+            task_metadata: true,
+            ..Default::default()
+        };
+        let markdown = "- [ ] File taxes ⏫ 📅 2024-04-15\n- [x] Book flights 🛫 2024-01-01 ✅ 2024-01-02\n- [ ] Someday 🔽";
+        let expected = "- ( |# B|< 2024-04-15) File taxes\n- (x|> 2024-01-01|@ 2024-01-02) Book flights\n- ( |# D) Someday";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        let markdown = "- [ ] File taxes ⏫ 📅 2024-04-15";
+        assert_eq!(
+            convert_markdown_to_neorg(markdown)?,
+            "- ( ) File taxes ⏫ 📅 2024-04-15"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_text() -> Result<()> {
+        let options = ConvertOptions {
+            escape_text: true,
+            ..Default::default()
+        };
+        let markdown = "# Paths\n\nInstall to /usr/local/ with *care*, not \\*all\\* of it.\n\n- **Bold** and ~~gone~~ -ish- H~2~O [/docs/](https://example.com/-a-)";
+        let expected = "* Paths\n\nInstall to \\/usr/local\\/ with /care/, not \\*all\\* of it.\n\n- *Bold* and -gone- \\-ish\\- H:,2,:O {https://example.com/-a-}[\\/docs\\/]";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        let markdown = "Install to /usr/local/";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_entities() -> Result<()> {
+        let options = ConvertOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let markdown = "Fish &amp; chips &copy; 2024 &#8212; &#x2713; &lt;b&gt;not bold&lt;/b&gt;\n&#35; not a heading, &bogus; &#0; `&amp;`\n\n```\n&copy;\n```";
+        let expected = "Fish \\& chips © 2024 — ✓ \\<b\\>not bold\\</b\\>\n\\# not a heading, &bogus; &#0; `&amp;`\n\n@code\n&copy;\n@end";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_punctuation() -> Result<()> {
+        let options = ConvertOptions {
+            normalize_punctuation: true,
+            ..Default::default()
+        };
+        let markdown = "# \u{201C}Quoted\u{201D}\n\nIt\u{2019}s 9\u{2013}5 \u{2014} or \u{2018}so\u{2019}\u{2026} `\u{201C}kept\u{201D}`\n\n```\n\u{2014}\n```";
+        let expected = "* \"Quoted\"\n\nIt's 9-5 -- or 'so'... `\u{201C}kept\u{201D}`\n\n@code\n\u{2014}\n@end";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        let markdown = "It\u{2019}s \u{2014} kept";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_emphasis_at_line_start_is_not_a_list() -> Result<()> {
+        let markdown = "*emphasis* at the start\n**bold** too, ***both***\n~~struck~~ and _under_\n\n- item\n*emphasis* after it\n\n* a real item with *emphasis*";
+        let expected = "/emphasis/ at the start\n*bold* too, */both/*\n-struck- and /under/\n\n- item\n/emphasis/ after it\n\n- a real item with /emphasis/";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        // Nor without the emphasis conversions, leaving the markers as they are.
+        let options = ConvertOptions {
+            passes: Passes {
+                emphasis: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let markdown = "*emphasis* at the start\n**bold** too";
+        assert_eq!(convert_with_options(markdown, &options)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_heading_level_clamped() -> Result<()> {
+        let markdown = "###### Six\n####### Seven\n######### Nine";
+        let conversion = convert_with_stats(markdown, &ConvertOptions::default())?;
+        let lines: Vec<usize> = conversion.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [2, 3]);
+        assert_eq!(
+            conversion.warnings[0].message,
+            "level 7 heading is made level 6, the deepest Neorg has"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks() -> Result<()> {
+        let markdown = "# Title\n\nSome *text*\n\n- one\n  - two\n\n```js\nlet x;\n```";
+        let conversion = convert_with_stats(markdown, &ConvertOptions::default())?;
+        let blocks: Vec<String> = conversion.blocks.iter().map(|b| b.to_string()).collect();
+        assert_eq!(
+            blocks,
+            [
+                "heading level 1: Title",
+                "text: Some /text/",
+                "list item depth 1: one",
+                "list item depth 2: two",
+                "code block lang=javascript (1 line)",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap() -> Result<()> {
+        let options = ConvertOptions {
+            wrap: Some(30),
+            ..Default::default()
+        };
+        let markdown = "# A heading that is longer than thirty characters\n\nThis paragraph is long enough to need wrapping at thirty columns, with a [link that has spaces](https://example.com) and `code with spaces` in it.\nA second line - joined on.\n\n```\na code line that is far longer than thirty characters\n```\n\n- a list item that is also longer than thirty characters";
+        let expected = "* A heading that is longer than thirty characters\n\nThis paragraph is long enough\nto need wrapping at thirty\ncolumns, with a\n{https://example.com}[link that has spaces]\nand `code with spaces` in it.\nA second line - joined on.\n\n@code\na code line that is far longer than thirty characters\n@end\n\n- a list item that is also longer than thirty characters";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        // Hard breaks are kept, and a wrapped line never starts with markup.
+        let markdown =
+            "Short lines,\\\nkept apart by a hard break.\n\nThis won't be a list item: one - two";
+        let expected =
+            "Short lines,\\\nkept apart by a hard break.\n\nThis won't be a list item: one -\ntwo";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        // Links and inline code longer than the limit are never broken up.
+        let markdown = "See `a piece of code longer than the limit` and <https://example.com/a/long/path> too.";
+        let expected = "See\n`a piece of code longer than the limit`\nand\n{https://example.com/a/long/path}[https://example.com/a/long/path]\ntoo.";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_mixed_content() -> Result<()> {
+        let markdown = "# Main Heading\n\n## Subheading\n\n- List item 1\n- [ ] Todo item\n\n```python\nprint(\"Hello, world!\")\n```";
+        let expected = "* Main Heading\n\n** Subheading\n\n- List item 1\n- ( ) Todo item\n\n@code python\nprint(\"Hello, world!\")\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_block_contents_untouched() -> Result<()> {
+        let markdown =
+            "Intro\n\n```python\n# a comment\n- not a list\nprint(\"[link](x)\")\n```\n\n# Heading";
+        let expected = "Intro\n\n@code python\n# a comment\n- not a list\nprint(\"[link](x)\")\n@end\n\n* Heading";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_fence_attributes() -> Result<()> {
+        let markdown = "```rust {.numberLines}\nfn main() {}\n```\n\n``` {.haskell #example}\nmain = pure ()\n```\n\n```{=html}\n<hr>\n```";
+        let expected = "@code rust\nfn main() {}\n@end\n\n@code haskell\nmain = pure ()\n@end\n\n@code html\n<hr>\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_language_aliases() -> Result<()> {
+        let markdown =
+            "```sh\nls\n```\n```Py\npass\n```\n```c++\nint x;\n```\n```zig\nconst x = 1;\n```";
+        let expected = "@code bash\nls\n@end\n@code python\npass\n@end\n@code cpp\nint x;\n@end\n@code zig\nconst x = 1;\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        let options = ConvertOptions {
+            languages: vec![("sh".to_string(), "sh".to_string())],
+            ..Default::default()
+        };
+        let markdown = "```sh\nls\n```\n```py\npass\n```";
+        let expected = "@code sh\nls\n@end\n@code py\npass\n@end";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pandoc_divs_and_heading_attributes() -> Result<()> {
+        let markdown =
+            "# Setup {#setup .unnumbered}\n\n::: warning\nBack up *first*.\n:::\n\nDone {-}\n----";
+        let expected = "* Setup\n\nBack up /first/.\n\n** Done";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_tilde_code_blocks() -> Result<()> {
+        let markdown = "~~~~js\n# not a heading\n~~~\n```\n~~~~\n\n# Heading";
+        let expected = "@code javascript\n# not a heading\n~~~\n```\n@end\n\n* Heading";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclosed_code_fence_runs_to_end() -> Result<()> {
+        let markdown = "# Setup\n\n```sh\n# install\n- not a list\n``\n";
+        let expected = "* Setup\n\n@code bash\n# install\n- not a list\n``\n@end\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        let options = ConvertOptions {
+            passes: Passes {
+                code: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let markdown = "~~~\n# install";
+        assert_eq!(convert_with_options(markdown, &options)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_indented_code_blocks() -> Result<()> {
+        let markdown =
+            "Some code:\n\n    # not a heading\n\n    - not a list\n\tindented with a tab\n\nAfter";
+        let expected = "Some code:\n\n@code\n# not a heading\n\n- not a list\nindented with a tab\n@end\n\nAfter";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_indented_list_items_are_not_code() -> Result<()> {
+        let markdown = "- Item\n\n    - Nested item\n\n        - Deeper item";
+        let expected = "- Item\n\n-- Nested item\n\n--- Deeper item";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_fence_in_list_item() -> Result<()> {
+        let markdown = "- Item\n\n  ```rust\n  fn main() {\n      run();\n  }\n  ```\n  - Nested\n    ~~~\n    **not bold**\n    ~~~\n- Next\n\n```\ntop\n```\n- New list";
+        let expected = "- Item\n\n  @code rust\n  fn main() {\n      run();\n  }\n  @end\n-- Nested\n   @code\n   **not bold**\n   @end\n- Next\n\n@code\ntop\n@end\n- New list";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_non_converted_content() -> Result<()> {
+        let markdown = "This is regular text.\n\nIt should be preserved as-is.";
+        let expected = "This is regular text.\n\nIt should be preserved as-is.";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_trailing_newline() -> Result<()> {
+        assert_eq!(convert_markdown_to_neorg("# Title\n")?, "* Title\n");
+        assert_eq!(convert_markdown_to_neorg("# Title")?, "* Title");
+        assert_eq!(convert_markdown_to_neorg("# Title\n\n\n")?, "* Title\n\n\n");
+        assert_eq!(
+            convert_markdown_to_neorg("```\ncode\n```")?,
+            "@code\ncode\n@end"
+        );
+        assert_eq!(convert_markdown_to_neorg("")?, "");
+
+        // Frontmatter is part of the input whose ending is kept, and a blank
+        // input has nothing to convert.
+        assert_eq!(
+            convert_markdown_to_neorg("---\ntitle: x\n---\n")?,
+            "@document.meta\ntitle: x\n@end\n"
+        );
+        assert_eq!(convert_markdown_to_neorg("   ")?, "   ");
+        assert_eq!(convert_markdown_to_neorg("  \n\n")?, "  \n\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_obsidian_links() -> Result<()> {
+        let markdown = "Check out [[My Page]] and [[Another Page With Spaces]]";
+        let expected = "Check out {:My Page.norg:} and {:Another Page With Spaces.norg:}";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_emphasis() -> Result<()> {
+        let markdown = "**bold** and *italic* together\n__bold__ and _italic_ too";
+        let expected = "*bold* and /italic/ together\n*bold* and /italic/ too";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_subscript_and_superscript() -> Result<()> {
+        let markdown = "H~2~O and E = mc^2^, 2^10^ is a ~lone~ one\n~~struck~~ and $x^2$ or a note[^1]\n\n[^1]: Note";
+        let expected = "H:,2,:O and E = mc:^2^, 2:^10^ is a ,lone, one\n-struck- and $x^2$ or a note{^ 1}\n\n^ 1\nNote";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_highlights() -> Result<()> {
+        let markdown = "A ==really important== point\n==Start== of a line, but x==y and a == b,\n`==code==` and $a==b$";
+        let expected = "A _really important_ point\n_Start_ of a line, but x==y and a == b,\n`==code==` and $a==b$";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_nested_emphasis() -> Result<()> {
+        let markdown = "**bold with *italic* inside**";
+        let expected = "*bold with /italic/ inside*";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_bold_italic() -> Result<()> {
+        let markdown =
+            "***both*** and ___both___, ***italic* then bold** and ***bold** then italic*";
+        let expected = "*/both/* and */both/*, */italic/ then bold* and /*bold* then italic/";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_mixed_emphasis_markers() -> Result<()> {
+        let markdown = "**_one_** _**two**_ *__three__* __*four*__ **bold _and_ more**";
+        let expected = "*/one/* /*two*/ /*three*/ */four/* *bold /and/ more*";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_emphasis_ignores_list_markers() -> Result<()> {
+        let markdown = "- **bold** item\n* *italic* item\n* plain item";
+        let expected = "- *bold* item\n- /italic/ item\n- plain item";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_emphasis_ignores_intraword_underscores() -> Result<()> {
+        let markdown = "call snake_case_name here";
+        let expected = "call snake_case_name here";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_strikethrough() -> Result<()> {
+        let markdown = "This is ~~wrong~~ and ~~also wrong~~, but a ~~ b stays.";
+        let expected = "This is -wrong- and -also wrong-, but a ~~ b stays.";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_inline_code() -> Result<()> {
+        let markdown = "Run `[not a link](x)` and `**not bold**`, but [a link](y).";
+        let expected = "Run `[not a link](x)` and `**not bold**`, but {y}[a link].";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_frontmatter() -> Result<()> {
+        let markdown = r#"---
+title: "My Note"
+description: Notes about things
+tags:
+  - project
+  - rust
+created: 2024-01-01
+aliases: [note, thing]
+---
+
+# My Note
+"#;
+        let expected = r#"@document.meta
+title: My Note
+description: Notes about things
+categories: [
+  project
+  rust
+]
+created: 2024-01-01
+aliases: [
+  note
+  thing
+]
+@end
+
+* My Note
+"#;
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_is_not_frontmatter() -> Result<()> {
+        let markdown = "Text\n\n---\n\nMore";
+        let expected = "Text\n\n___\n\nMore";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_large_document() -> Result<()> {
+        let section = "## Section\n\n- [ ] Todo with [a link](https://example.com)\n  - **bold** item\n\n```sh\necho hi\n```\n";
+        let converted = "** Section\n\n- ( ) Todo with {https://example.com}[a link]\n-- *bold* item\n\n@code bash\necho hi\n@end\n";
+        let markdown = section.repeat(5_000);
+        assert_eq!(
+            convert_markdown_to_neorg(&markdown)?,
+            converted.repeat(5_000)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_obsidian_link_aliases() -> Result<()> {
+        let markdown = "See [[My Page|this page]] and [[Other Page]]";
+        let expected = "See {:My Page.norg:}[this page] and {:Other Page.norg:}";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_obsidian_heading_links() -> Result<()> {
+        let markdown = "See [[My Page#Some Section]], [[#Local Section]] and [[Page#Part|alias]]";
+        let expected =
+            "See {:My Page.norg:# Some Section}, {# Local Section} and {:Page.norg:# Part}[alias]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_obsidian_block_ids() -> Result<()> {
+        let markdown = "A paragraph to quote. ^quote-1\n\n- one\n- two\n\n^list\n\nAn exponent 2^10 and a note[^1].\n\n[^1]: Note.";
+        let expected =
+            "A paragraph to quote.\n\n- one\n- two\n\nAn exponent 2^10 and a note{^ 1}.\n\n^ 1\nNote.";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_obsidian_block_links() -> Result<()> {
+        let markdown = "See [[My Page#^quote-1]], [[Page#^abc|this quote]], [[#^list|the list]] and [[#^list]]";
+        let expected = "See {:My Page.norg:}, {:Page.norg:}[this quote], the list and [[#^list]]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_obsidian_embeds() -> Result<()> {
+        let markdown = "![[diagram.png]]\n![[Other Note]]\n![[Other Note#Part]]\n![[paper.pdf]]";
+        let expected =
+            "{image:diagram.png}\n{:Other Note.norg:}\n{:Other Note.norg:# Part}\n{/ paper.pdf}";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_styled_link_text() -> Result<()> {
+        let markdown = "[**bold**](https://example.com/*x*) and [`code` _it_](https://example.com/~a/~b~) and [==mark== ~~old~~][r] and [[Page|**Page**]]\n\n[r]: https://example.com/a__b__c";
+        let expected = "{https://example.com/*x*}[*bold*] and {https://example.com/~a/~b~}[`code` /it/] and {https://example.com/a__b__c}[_mark_ -old-] and {:Page.norg:}[*Page*]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropped_definitions_keep_ending() -> Result<()> {
+        let markdown = "See [x][r]\n\n[r]: http://e.com";
+        assert_eq!(
+            convert_markdown_to_neorg(markdown)?,
+            "See {http://e.com}[x]"
+        );
+
+        let markdown = "See [x][r]\n\n[r]: http://e.com\n";
+        assert_eq!(
+            convert_markdown_to_neorg(markdown)?,
+            "See {http://e.com}[x]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_reference_links() -> Result<()> {
+        let markdown = "See [the docs][Docs] and [docs][].\n\n```\n[docs]: not-a-definition\n```\n\n[DOCS]: https://docs.rs \"Docs\"\n[docs]: https://ignored.example";
+        let expected = "See {https://docs.rs}[the docs] and {https://docs.rs}[docs].\n\n@code\n[docs]: not-a-definition\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dangling_reference_link() -> Result<()> {
+        let markdown = "A [dangling][nowhere] link";
+        let expected = "A [dangling][nowhere] link";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_unused_reference_definitions() -> Result<()> {
+        let markdown =
+            "Set the [timeout][] like so:\n\n[retries]: 3\n[timeout]: 30s\n[Timeout]: 60s";
+        let expected = "Set the {30s}[timeout] like so:\n\n[retries]: 3";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_sizes() -> Result<()> {
+        let markdown = "![[diagram.png|200]] ![[photo.jpg|A photo|640x480]] ![[Note|300]]\n![Map|200](img/map.png) ![|100x50](a.png) ![A|B](b.png) ![Logo|64][logo]\n\n[logo]: logo.svg";
+        let expected = "{image:diagram.png}[200px] {image:photo.jpg}[A photo (640x480px)] {:Note.norg:}[300]\n{image:img/map.png}[Map (200px)] {image:a.png}[100x50px] {image:b.png}[A|B] {image:logo.svg}[Logo (64px)]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_reference_images() -> Result<()> {
+        let markdown = "![A diagram][diagram] and ![][Logo]\n\n[diagram]: assets/diagram.png \"Diagram\"\n[logo]: logo.svg";
+        let expected = "{image:assets/diagram.png}[A diagram] and {image:logo.svg}[]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        let markdown = "![Missing][nowhere]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_footnote() -> Result<()> {
+        let markdown = "A claim[^1] that needs a source.\n\n[^1]: The source.\n";
+        let expected = "A claim{^ 1} that needs a source.\n\n^ 1\nThe source.\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_footnote_before_colon() -> Result<()> {
+        let markdown = "Note[^a]:\n\n```\ncode\n```\n[^a]: After the code.";
+        let expected = "Note{^ a}:\n\n@code\ncode\n@end\n^ a\nAfter the code.";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undefined_footnote() -> Result<()> {
+        let markdown = "A claim[^missing] with no source";
+        let expected = "A claim[^missing] with no source";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_footnote() -> Result<()> {
+        let markdown = "First[^1] and second^[An *inline* `note`].\n\n[^1]: Defined.\n";
+        let expected = "First{^ 1} and second{^ 2}.\n\n^ 1\nDefined.\n\n^ 2\nAn /inline/ `note`\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("notes"))?;
+        fs::write(
+            dir.path().join("notes/index.md"),
+            "See [Other](../other.md)\n",
+        )?;
+
+        let output = dir.path().join("out/notes/index.norg");
+        convert_file(&dir.path().join("notes/index.md"), &output)?;
+
+        assert_eq!(
+            fs::read_to_string(output)?,
+            "See {:../other.norg:}[Other]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_file_errors() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("latin1.md");
+        fs::write(&input, b"caf\xe9\n")?;
+        let output = dir.path().join("latin1.norg");
+
+        let err = convert_file(&input, &output).unwrap_err();
+        assert!(
+            matches!(&err, ConvertError::Utf8 { path, .. } if *path == input),
+            "{:?}",
+            err
+        );
+        assert!(!output.exists());
+
+        let missing = dir.path().join("missing.md");
+        let err = convert_file(&missing, &output).unwrap_err();
+        assert!(matches!(err, ConvertError::Read { .. }), "{:?}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_md_link() -> Result<()> {
+        let markdown = "[see](notes/todo.md) and [ref][todo]\n\n[todo]: ../todo.md";
+        let expected = "{:notes/todo.norg:}[see] and {:../todo.norg:}[ref]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_md_link_with_anchor() -> Result<()> {
+        let markdown = "[setup](guide.md#Getting started)";
+        let expected = "{:guide.norg:# Getting started}[setup]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_md_link() -> Result<()> {
+        let markdown = "[readme](https://example.com/README.md) and [pdf](docs/a.pdf)";
+        let expected = "{https://example.com/README.md}[readme] and {docs/a.pdf}[pdf]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_meta_title_from_frontmatter() -> Result<()> {
+        let options = ConvertOptions {
+            add_meta: true,
+            ..Default::default()
+        };
+        let markdown = "---\ntitle: From frontmatter\n---\n# From heading\n";
+        let expected = "@document.meta\ntitle: From frontmatter\n@end\n\n* From heading\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_meta_title_from_heading() -> Result<()> {
+        let options = ConvertOptions {
+            add_meta: true,
+            ..Default::default()
+        };
+        let markdown = "```\n# Not a heading\n```\n## Section\n# From heading\n";
+        let expected = "@document.meta\ntitle: From heading\n@end\n\n@code\n# Not a heading\n@end\n** Section\n* From heading\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_meta_title_from_file_stem() -> Result<()> {
+        let options = ConvertOptions {
+            add_meta: true,
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("meeting notes.md"), "No headings here.\n")?;
+
+        let output = dir.path().join("meeting notes.norg");
+        convert_file_with_options(&dir.path().join("meeting notes.md"), &output, &options)?;
+
+        let expected = "@document.meta\ntitle: meeting notes\n@end\n\nNo headings here.\n";
+        assert_eq!(fs::read_to_string(output)?, expected);
+
+        let conversion = convert_named("No headings here.\n", "meeting notes", &options)?;
+        assert_eq!(conversion.output, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_html_comment() -> Result<()> {
+        let markdown =
+            "Some text <!-- a *private* note --> here\n<!-- whole line -->\n`<!-- code -->`";
+        let expected = "Some text %a *private* note% here\n%whole line%\n`<!-- code -->`";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_html_comment() -> Result<()> {
+        let markdown = "Before\n<!--\n# Not a heading\nsecond line -->\nAfter\n\n```html\n<!--\nkept\n-->\n```\n";
+        let expected = "Before\n@comment\n# Not a heading\nsecond line\n@end\nAfter\n\n@code html\n<!--\nkept\n-->\n@end\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclosed_html_comment() -> Result<()> {
+        let markdown = "<!-- never closed\n# Heading";
+        let expected = "<!-- never closed\n* Heading";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_break() -> Result<()> {
+        let markdown = "First line<br>second line<br/>\nthird line";
+        let expected = "First line\\\nsecond line\\\nthird line";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        // The break carries on the list item or quote it's in, and a heading
+        // or table row can't break.
+        let markdown =
+            "- one<br>two\n  - nested<br>more\n- three<br>\n\n> quoted<br>on\n\n# A<br>heading";
+        let expected =
+            "- one\\\n  two\n-- nested\\\n   more\n- three\n\n> quoted\\\n> on\n\n* A heading";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+
+        let markdown = "| a | b |\n|---|---|\n| x<br>y | z |";
+        let expected = "@table\na | b\n-\nx y | z\n@end";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_emphasis() -> Result<()> {
+        let markdown = "<b>Bold</b>, <strong>strong</strong>, <em>em</em> and <u>underlined</u>";
+        let expected = "*Bold*, *strong*, /em/ and _underlined_";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_unknown_html() -> Result<()> {
+        let markdown = "A <span style=\"color: red\">red</span> word in a Vec<String> `<span>`";
+        let expected = "A red word in a Vec<String> `<span>`";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_html() -> Result<()> {
+        let options = ConvertOptions {
+            html: false,
+            ..Default::default()
+        };
+        let markdown = "<b>Bold</b><br>";
+        assert_eq!(convert_with_options(markdown, &options)?, markdown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_escaped_asterisk() -> Result<()> {
+        let markdown = "\\*not emphasis\\* and 2 \\* 3 = 6\\.";
+        let expected = "\\*not emphasis\\* and 2 \\* 3 = 6.";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_escaped_hash() -> Result<()> {
+        let markdown = "\\# not a heading\nissue \\#42\n1\\. not a list";
+        let expected = "\\# not a heading\nissue #42\n1. not a list";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_escaped_bracket() -> Result<()> {
+        let markdown = "\\[not a link\\](https://example.com) and a \\`literal\\`";
+        let expected = "\\[not a link\\](https://example.com) and a \\`literal\\`";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_blank_lines() -> Result<()> {
+        let options = ConvertOptions {
+            collapse_blank_lines: true,
+            ..Default::default()
+        };
+        let markdown = "\n# Title\n\n\n\n[Text][ref]\n\n[ref]: https://example.com\n\n\n- item\n";
+        let expected = "* Title\n\n{https://example.com}[Text]\n\n- item\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        // Blank lines at the end go too, keeping the final newline.
+        assert_eq!(convert_with_options("a\n\n\n\n\n", &options)?, "a\n");
+        assert_eq!(convert_with_options("a\n\n  \n", &options)?, "a\n");
+        assert_eq!(convert_with_options("a", &options)?, "a");
+        assert_eq!(convert_with_options("\n\n\n", &options)?, "\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_blank_lines_keeps_code() -> Result<()> {
+        let options = ConvertOptions {
+            collapse_blank_lines: true,
+            ..Default::default()
+        };
+        let markdown = "Text\n\n\n```\nfn a() {}\n\n\n\nfn b() {}\n```\n\n\nMore\n";
+        let expected = "Text\n\n@code\nfn a() {}\n\n\n\nfn b() {}\n@end\n\nMore\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hard_break_with_spaces() -> Result<()> {
+        let markdown = "Roses are red,  \nviolets are blue.   \n\nNew paragraph";
+        let expected = "Roses are red,\\\nviolets are blue.\n\nNew paragraph";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hard_break_with_backslash() -> Result<()> {
+        let markdown = "First line\\\nsecond line\\\\\nthird line\\\n- a list";
+        let expected = "First line\\\nsecond line\\\\\nthird line\n- a list";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_definition_list() -> Result<()> {
+        let markdown = "Markdown\n: A lightweight markup language\n\nNeorg\n: A note taking format\n: A Neovim plugin\n";
+        let expected = "$ Markdown\nA lightweight markup language\n\n$$ Neorg\nA note taking format\n\nA Neovim plugin\n$$\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stray_definition_line() -> Result<()> {
+        let markdown = "Some text\n\n: not a definition\n- item\n: nor this";
+        let expected = "Some text\n\n: not a definition\n- item\n: nor this";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_math() -> Result<()> {
+        let markdown = "Euler: $e^{i\\pi} + 1 = 0$, or $$a_1 * b_2$$, for $5 or $10";
+        let expected = "Euler: $e^{i\\pi} + 1 = 0$, or $a_1 * b_2$, for $5 or $10";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_math() -> Result<()> {
+        let markdown = "Before\n$$\n\\sum_{i=1}^n i = \\frac{n(n+1)}{2}\n$$\n$$ E = mc^2 $$\nAfter";
+        let expected = "Before\n@math\n\\sum_{i=1}^n i = \\frac{n(n+1)}{2}\n@end\n@math\nE = mc^2\n@end\nAfter";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_passes() -> Result<()> {
+        let options = ConvertOptions {
+            passes: Passes {
+                links: false,
+                emphasis: false,
+                tables: false,
+                code: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let markdown = "# Title\n\nA [link](https://example.com) to [[Page]] with **bold**\n\n| a | b |\n| - | - |\n\n```rust\nlet x = 1;\n```\n\n    indented\n\n[ref]: https://example.com\n";
+        let expected = "* Title\n\nA [link](https://example.com) to [[Page]] with **bold**\n\n| a | b |\n| - | - |\n\n```rust\nlet x = 1;\n```\n\n    indented\n\n[ref]: https://example.com\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_default_options() -> Result<()> {
+        let options = ConvertOptions {
+            rewrite_md_links: false,
+            tags: false,
+            html: false,
+            add_meta: true,
+            passes: Passes {
+                emphasis: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let markdown = "# Notes\n\nSee [other](other.md), #todo, <b>**bold**</b>";
+        let expected = "@document.meta\ntitle: Notes\n@end\n\n* Notes\n\nSee {other.md}[other], #todo, <b>**bold**</b>";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_some_passes() -> Result<()> {
+        let options = ConvertOptions {
+            passes: Passes {
+                headings: true,
+                lists: true,
+                ..Passes::none()
+            },
+            ..Default::default()
+        };
+        let markdown = "# Title\n\n- [ ] task with *emphasis*\n1. one\n";
+        let expected = "* Title\n\n- [ ] task with *emphasis*\n~ one\n";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_crlf() -> Result<()> {
+        let markdown = "# Title\r\n\r\n- item\r\n\r\n```\r\ncode\r\n```\r\n";
+        let expected = "* Title\r\n\r\n- item\r\n\r\n@code\r\ncode\r\n@end\r\n";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_ending_option() -> Result<()> {
+        let markdown = "---\r\ntitle: Note\r\n---\r\n# Title\r\nText";
+        let options = ConvertOptions {
+            line_ending: LineEnding::Lf,
+            ..Default::default()
+        };
+        let expected = "@document.meta\ntitle: Note\n@end\n\n* Title\nText";
+        assert_eq!(convert_with_options(markdown, &options)?, expected);
+
+        let options = ConvertOptions {
+            line_ending: LineEnding::Crlf,
+            ..Default::default()
+        };
+        let expected = "* Title\r\nText\r\n";
+        assert_eq!(convert_with_options("# Title\nText\n", &options)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_callout() -> Result<()> {
+        let markdown =
+            "> [!NOTE] Remember this\n> First line of the body.\n> Second line.\n\nAfter";
+        let expected =
+            "> *Note: Remember this*\n> First line of the body.\n> Second line.\n\nAfter";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_alerts() -> Result<()> {
+        let markdown = "> [!IMPORTANT]\n> Back up first.\n\n> [!CAUTION]\n> This deletes files.\n\n> [!NOTE]\n> a\n\n> [!TIP]\n> b\n\n> [!WARNING]\n> c";
+        let expected = "> *Important*\n> Back up first.\n\n> *Caution*\n> This deletes files.\n\n> *Note*\n> a\n\n> *Tip*\n> b\n\n> *Warning*\n> c";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_foldable_callout_without_title() -> Result<()> {
+        let markdown = "> [!faq]-\n> Hidden answer\n\n> [!custom-type]+ Open\n> Body";
+        let expected = "> *Question*\n> Hidden answer\n\n> *Custom-type: Open*\n> Body";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let markdown = "# Title\n\nSee [docs][] and [[Page]], ![img](a.png)\n\nSub\n---\n\n- [x] done\n- item\n1. one\n\n```\ncode\n```\n\n| a |\n| - |\n\n[docs]: https://docs.rs\n";
+        let stats = convert_with_stats(markdown, &ConvertOptions::default())?.stats;
+        let expected = Stats {
+            headings: 2,
+            list_items: 2,
+            tasks: 1,
+            links: 3,
+            code_blocks: 1,
+            tables: 1,
+        };
+        assert_eq!(stats, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_warnings() -> Result<()> {
+        let markdown = "---\ntitle: Notes\n---\n# Notes\n\n<table><tr><td>A <b>cell</b></td></tr></table>\n\n`<video>` and <span>kept</span>\n\n::: aside\nText\n:::\n\nSee [this][missing].\n\n```html\n<table>\n```\n\n```\nnever closed";
+        let warnings: Vec<String> = convert_with_stats(markdown, &ConvertOptions::default())?
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let expected = [
+            "line 6: raw HTML `<table>`, `<tr>`, `<td>` can't be converted, so is left as it is",
+            "line 10: Neorg has nothing like the `aside` div, so only its content is kept",
+            "line 14: no definition for the reference `missing`, so it's left as it is",
+            "line 20: code fence is never closed, so runs to the end of the document",
+        ];
+        assert_eq!(warnings, expected);
+
+        let options = ConvertOptions {
+            html: false,
+            ..Default::default()
+        };
+        let warnings = convert_with_stats("<table></table>", &options)?.warnings;
+        assert!(warnings.is_empty());
+
+        let warnings =
+            convert_with_stats("| a | b |\n| - |\n", &ConvertOptions::default())?.warnings;
+        assert_eq!(
+            warnings[0].message,
+            "table has 2 header cells but 1 in its delimiter row, so is left as text"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict() -> Result<()> {
+        let options = ConvertOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let markdown = "# Notes\n\n```rust\nfn main() {}\n";
+        match convert_with_options(markdown, &options) {
+            Err(ConvertError::Malformed { line, message }) => {
+                assert_eq!(line, 3);
+                assert_eq!(
+                    message,
+                    "code fence is never closed, so runs to the end of the document"
+                );
+            }
+            result => panic!("expected a malformed markdown error, got {:?}", result),
+        }
+
+        let markdown = "# Notes\n\n```rust\nfn main() {}\n```\n";
+        assert!(convert_with_options(markdown, &options).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_tags() -> Result<()> {
+        let markdown =
+            "# Heading\n\nWorking on #project/md2norg today, see issue #42.\n#idea\n`#not-a-tag`";
+        let expected = "@document.meta\ncategories: [\n  project/md2norg\n  idea\n]\n@end\n\n* Heading\n\nWorking on #project/md2norg today, see issue #42.\n\\#idea\n`#not-a-tag`";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_merge_with_frontmatter() -> Result<()> {
+        let markdown = "---\ntags: [idea]\n---\nAn #idea and a #plan";
+        let expected =
+            "@document.meta\ncategories: [\n  idea\n  plan\n]\n@end\n\nAn #idea and a #plan";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_tags() -> Result<()> {
+        let options = ConvertOptions {
+            tags: false,
+            ..Default::default()
+        };
+        let markdown = "An #idea";
+        assert_eq!(convert_with_options(markdown, &options)?, "An #idea");
+        Ok(())
+    }
+
+    #[test]
+    fn test_links_with_brackets_and_parentheses() -> Result<()> {
+        let markdown =
+            "[Mercury](https://en.wikipedia.org/wiki/Mercury_(planet)) and [see [1]](#refs)";
+        let expected =
+            "{https://en.wikipedia.org/wiki/Mercury_(planet)}[Mercury] and {#refs}[see \\[1\\]]";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_links_with_titles_and_escaped_brackets() -> Result<()> {
+        let markdown = "[a \\[b\\]](https://example.com \"Title\") [![CI](badge.svg)](https://ci.example.com) [not a link] (here)";
+        let expected = "{https://example.com}[a \\[b\\]] {https://ci.example.com}[{image:badge.svg}[CI]] [not a link] (here)";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_email_autolinks() -> Result<()> {
+        let markdown =
+            "Mail <jane.doe+notes@example.com> or see <https://example.com>, not a <name> placeholder";
+        let expected = "Mail {mailto:jane.doe+notes@example.com}[jane.doe+notes@example.com] or see {https://example.com}[https://example.com], not a <name> placeholder";
+        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_markdown_links() -> Result<()> {
+        let input = r#"
+[Basic link](https://example.com)
+[Reference link][ref]
+[Implicit reference link][]
+<https://example.com>
+![Image](image.jpg)
+![Image with title](image.jpg "Title")
+![Reference image][img-ref]
+
+[ref]: https://example.com "Reference Title"
+[img-ref]: image.jpg "Image Reference Title"
+"#;
+
+        let expected_output = r#"
+{https://example.com}[Basic link]
+{https://example.com}[Reference link]
+[Implicit reference link][]
+{https://example.com}[https://example.com]
+{image:image.jpg}[Image]
+{image:image.jpg}[Image with title]
+{image:image.jpg}[Reference image]
+"#;
+
+        let actual = convert_markdown_to_neorg(input)?;
+        println!("{}", &actual);
+        assert_eq!(actual, expected_output);
+        Ok(())
+    }
+}
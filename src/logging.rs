@@ -0,0 +1,38 @@
+//! Reporting of progress and errors on stderr.
+
+use std::io::Write;
+
+use env_logger::WriteStyle;
+use log::{Level, LevelFilter};
+
+/// Sets up the logger that the `log` macros write to: errors only when
+/// `quiet`, each converted file by default, and more detail for each
+/// `verbose` level. Colours are used when stderr is a terminal and `NO_COLOR`
+/// isn't set, unless `color` says otherwise.
+pub fn init(quiet: bool, verbose: u8, color: Option<bool>) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let style = match color {
+        Some(true) => WriteStyle::Always,
+        Some(false) => WriteStyle::Never,
+        None => WriteStyle::Auto,
+    };
+
+    env_logger::Builder::new()
+        .filter_level(level)
+        .write_style(style)
+        .format(|buf, record| {
+            // Information is the tool's normal output, so isn't labelled.
+            if record.level() == Level::Info {
+                return writeln!(buf, "{}", record.args());
+            }
+            let style = buf.default_level_style(record.level());
+            let label = record.level().as_str().to_lowercase();
+            writeln!(buf, "{style}{label}{style:#}: {}", record.args())
+        })
+        .init();
+}
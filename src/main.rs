@@ -1,11 +1,37 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use clap::Parser;
-use regex::Regex;
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use filetime::FileTime;
+use glob::{MatchOptions, Pattern};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::Level;
+use md2norg::{
+    Block, ConvertError, ConvertOptions, LineEnding, Passes, Stats, Warning, DEFAULT_LANGUAGES,
+};
+use notify::{EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use serde::Serialize;
 use walkdir::WalkDir;
 
+mod archive;
+mod config;
+mod logging;
+mod paths;
+
+use config::Config;
+
+/// How long to wait for further changes before re-converting in watch mode.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
 /// md2norg - a markdown to neorg file converter.
 ///
 /// This tool converts notes kept in a markdown format to neorg (.norg). This is
@@ -14,218 +40,1180 @@ use walkdir::WalkDir;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Input directory containing markdown files
+    /// Input directory containing markdown files, a single file, a glob
+    /// pattern such as `notes/2023-*.md`, or a zip archive of notes to convert
+    /// into --output. Can be repeated to convert several at once. If omitted,
+    /// markdown is read from stdin and the converted neorg is written to
+    /// stdout.
     #[arg(short, long)]
-    input: String,
+    input: Vec<String>,
 
     /// Output directory for converted files (optional), otherwise existing
-    /// directory is used.
-    #[arg(short, long)]
+    /// directory is used. When the input is a single file, this can also be
+    /// the path of the file to write.
+    #[arg(short, long, requires = "input")]
     output: Option<String>,
 
     /// Process subdirectories recursively
     #[arg(short, long)]
     recursive: bool,
+
+    /// Write every converted file directly into the output directory instead
+    /// of mirroring the input's subdirectories. Files with the same name get a
+    /// numbered suffix.
+    #[arg(long)]
+    flatten: bool,
+
+    /// Number of files to convert in parallel. Defaults to the number of CPUs.
+    #[arg(
+        short,
+        long,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    jobs: Option<usize>,
+
+    /// Stop at the first file that fails to convert, rather than converting
+    /// the rest and reporting how many failed
+    #[arg(long, conflicts_with = "watch")]
+    fail_fast: bool,
+
+    /// Leave Obsidian #tags alone instead of collecting them into the
+    /// document metadata
+    #[arg(long)]
+    no_tags: bool,
+
+    /// Leave links to other markdown notes pointing at the `.md` file, instead
+    /// of its neorg conversion
+    #[arg(long)]
+    keep_md_links: bool,
+
+    /// Turn the Obsidian Tasks plugin's emoji dates and priorities into
+    /// neorg TODO extensions
+    #[arg(long)]
+    task_metadata: bool,
+
+    /// Escape characters in the text that neorg would read as markup, like
+    /// the slashes in /usr/local/
+    #[arg(long)]
+    escape_text: bool,
+
+    /// Decode HTML entities like &amp; and &#8212; to the characters they
+    /// stand for
+    #[arg(long)]
+    decode_entities: bool,
+
+    /// Replace curly quotes, en and em dashes and ellipses with plain ASCII
+    /// punctuation
+    #[arg(long)]
+    normalize_punctuation: bool,
+
+    /// Give code blocks in this language another name, e.g. `--language
+    /// sh=zsh`, overriding the built-in ones such as `js=javascript`. Can be
+    /// repeated
+    #[arg(long, value_name = "FROM=TO", value_parser = parse_language)]
+    language: Vec<(String, String)>,
+
+    /// Leave raw HTML tags in the output instead of converting or removing
+    /// them
+    #[arg(long)]
+    keep_html: bool,
+
+    /// Add a document metadata block with a title to every converted file,
+    /// taken from its frontmatter, first heading or file name
+    #[arg(long)]
+    add_meta: bool,
+
+    /// Collapse runs of blank lines outside of code blocks down to one
+    #[arg(long)]
+    collapse_blank_lines: bool,
+
+    /// Reflow paragraphs to lines of at most this many characters, leaving
+    /// headings, lists, tables and code as they are
+    #[arg(
+        long,
+        value_name = "COLS",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    wrap: Option<usize>,
+
+    /// Treat anything that can't be converted faithfully, such as an
+    /// unclosed code fence, as an error, leaving that file unconverted
+    #[arg(long)]
+    strict: bool,
+
+    /// Line endings to write: the same as the input's, or always lf or crlf
+    #[arg(long, value_enum, default_value_t = LineEndingArg::Keep)]
+    line_ending: LineEndingArg,
+
+    /// Conversions to skip, leaving that markdown syntax as it is
+    #[arg(long, value_name = "PASSES", value_delimiter = ',')]
+    skip: Vec<Pass>,
+
+    /// Run only these conversions
+    #[arg(
+        long,
+        value_name = "PASSES",
+        value_delimiter = ',',
+        conflicts_with = "skip"
+    )]
+    only: Vec<Pass>,
+
+    /// After converting, keep watching the input and re-convert markdown
+    /// files as they're created or changed
+    #[arg(long, conflicts_with = "dry_run")]
+    watch: bool,
+
+    /// Report what would be converted, and any existing files that would be
+    /// overwritten, without writing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Give each converted file the modification time and permissions of the
+    /// markdown file it came from
+    #[arg(long)]
+    preserve_timestamps: bool,
+
+    /// Skip files whose converted output already exists, rather than
+    /// overwriting it
+    #[arg(long, alias = "skip-existing")]
+    no_overwrite: bool,
+
+    /// Copy the other files under the input directory, such as images and
+    /// PDFs, to the same place under the output directory, so links to them
+    /// from the converted notes still work
+    #[arg(long, requires = "output", conflicts_with = "flatten")]
+    copy_assets: bool,
+
+    /// Before converting each markdown file, save a copy of it next to it with
+    /// a `.bak` suffix
+    #[arg(long)]
+    backup: bool,
+
+    /// Save the copies made before converting in this directory instead,
+    /// mirroring their place under the input. Implies --backup
+    #[arg(long, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+
+    /// Delete each markdown file once its conversion has been written
+    #[arg(long)]
+    delete_source: bool,
+
+    /// Only convert files whose path relative to the input, or one of its
+    /// directories, matches this glob. Can be repeated
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Don't convert files whose path relative to the input, or one of its
+    /// directories, matches this glob, e.g. `templates`. Can be repeated, and
+    /// wins over --include
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Also look in hidden directories, such as `.obsidian`, whose names
+    /// start with a `.`
+    #[arg(long)]
+    include_hidden: bool,
+
+    /// File extensions to treat as markdown
+    #[arg(
+        long = "ext",
+        value_name = "EXT",
+        value_delimiter = ',',
+        default_value = "md,markdown,mdown,mkd,mdx"
+    )]
+    extensions: Vec<String>,
+
+    /// File extension to give converted files
+    #[arg(
+        long,
+        value_name = "EXT",
+        default_value = "norg",
+        value_parser = parse_output_extension
+    )]
+    output_extension: String,
+
+    /// Don't report each converted file, only errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Report file sizes and timings for each converted file, and a summary
+    /// of the run. Repeat for more detail
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Never colour the output, even on a terminal
+    #[arg(long, conflicts_with = "force_color")]
+    no_color: bool,
+
+    /// Colour the output even when it isn't going to a terminal
+    #[arg(long)]
+    force_color: bool,
+
+    /// After converting, write a JSON report of each converted file's size
+    /// and what was converted in it to this file
+    #[arg(long, value_name = "PATH")]
+    stats_json: Option<PathBuf>,
+
+    /// Instead of converting, print how each block of the input is
+    /// classified to stderr, for debugging a conversion
+    #[arg(long, hide = true, conflicts_with_all = ["watch", "dry_run"])]
+    print_tree: bool,
+
+    /// Read default options from this file, instead of md2norg.toml in the
+    /// current directory
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a completion script for a shell to stdout
+    Completions { shell: Shell },
+}
+
+impl Args {
+    /// Parses the command line, filling in anything it doesn't set from the
+    /// config file.
+    fn load() -> Result<Self> {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches)?;
+        let config = config::load(args.config.as_deref())?;
+        args.apply_config(config, &matches);
+        Ok(args)
+    }
+
+    fn apply_config(&mut self, config: Config, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        let conversions = config.conversions;
+
+        if let Some(recursive) = config.recursive.filter(|_| unset("recursive")) {
+            self.recursive = recursive;
+        }
+        if let Some(output) = config.output.filter(|_| unset("output")) {
+            self.output = Some(output);
+        }
+        if let Some(extensions) = config.extensions.filter(|_| unset("extensions")) {
+            self.extensions = extensions;
+        }
+        if let Some(overwrite) = config.overwrite.filter(|_| unset("no_overwrite")) {
+            self.no_overwrite = !overwrite;
+        }
+        if let Some(tags) = conversions.tags.filter(|_| unset("no_tags")) {
+            self.no_tags = !tags;
+        }
+        if let Some(html) = conversions.html.filter(|_| unset("keep_html")) {
+            self.keep_html = !html;
+        }
+        if let Some(add_meta) = conversions.add_meta.filter(|_| unset("add_meta")) {
+            self.add_meta = add_meta;
+        }
+        if let Some(collapse) = conversions
+            .collapse_blank_lines
+            .filter(|_| unset("collapse_blank_lines"))
+        {
+            self.collapse_blank_lines = collapse;
+        }
+    }
+
+    fn is_markdown(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| {
+            self.extensions
+                .iter()
+                .any(|md| ext.eq_ignore_ascii_case(md.trim_start_matches('.')))
+        })
+    }
+
+    fn convert_options(&self) -> ConvertOptions {
+        let mut languages = self.language.clone();
+        languages.extend(
+            DEFAULT_LANGUAGES
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string())),
+        );
+        ConvertOptions {
+            languages,
+            rewrite_md_links: !self.keep_md_links,
+            tags: !self.no_tags,
+            task_metadata: self.task_metadata,
+            escape_text: self.escape_text,
+            decode_entities: self.decode_entities,
+            normalize_punctuation: self.normalize_punctuation,
+            html: !self.keep_html,
+            add_meta: self.add_meta,
+            collapse_blank_lines: self.collapse_blank_lines,
+            wrap: self.wrap,
+            strict: self.strict,
+            passes: self.passes(),
+            line_ending: match self.line_ending {
+                LineEndingArg::Keep => LineEnding::Keep,
+                LineEndingArg::Lf => LineEnding::Lf,
+                LineEndingArg::Crlf => LineEnding::Crlf,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn passes(&self) -> Passes {
+        if self.only.is_empty() {
+            let mut passes = Passes::default();
+            for pass in &self.skip {
+                *pass.enabled(&mut passes) = false;
+            }
+            passes
+        } else {
+            let mut passes = Passes::none();
+            for pass in &self.only {
+                *pass.enabled(&mut passes) = true;
+            }
+            passes
+        }
+    }
+}
+
+/// Checks an `--output-extension` is a single file extension, dropping any
+/// leading `.`.
+fn parse_output_extension(ext: &str) -> Result<String> {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+    let valid = ext
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if ext.is_empty() || !valid || ext.starts_with('.') || ext.ends_with('.') {
+        bail!("expected a file extension such as `norg`, not `{}`", ext);
+    }
+    Ok(ext.to_string())
+}
+
+/// Splits a `--language` mapping into the language and its new name.
+fn parse_language(mapping: &str) -> Result<(String, String)> {
+    match mapping.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => bail!("expected FROM=TO, such as `js=javascript`"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LineEndingArg {
+    Keep,
+    Lf,
+    Crlf,
+}
+
+/// A conversion pass that can be chosen with `--skip` or `--only`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Pass {
+    Headings,
+    Lists,
+    Todos,
+    Links,
+    Code,
+    Emphasis,
+    Tables,
+}
+
+impl Pass {
+    fn enabled(self, passes: &mut Passes) -> &mut bool {
+        match self {
+            Pass::Headings => &mut passes.headings,
+            Pass::Lists => &mut passes.lists,
+            Pass::Todos => &mut passes.todos,
+            Pass::Links => &mut passes.links,
+            Pass::Code => &mut passes.code,
+            Pass::Emphasis => &mut passes.emphasis,
+            Pass::Tables => &mut passes.tables,
+        }
+    }
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::load()?;
+    if let Some(Command::Completions { shell }) = args.command {
+        clap_complete::generate(shell, &mut Args::command(), "md2norg", &mut io::stdout());
+        return Ok(());
+    }
+    let color = match (args.force_color, args.no_color) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    };
+    logging::init(args.quiet, args.verbose, color);
+    let options = args.convert_options();
+
+    if args.print_tree {
+        return print_tree(&args, &options);
+    }
+
+    if args.input.is_empty() {
+        let content = io::read_to_string(io::stdin())?;
+        let conversion = md2norg::convert_with_stats(&content, &options)?;
+        log_warnings(Path::new("<stdin>"), &conversion.warnings);
+        print!("{}", conversion.output);
+        return Ok(());
+    }
+
+    let inputs: Vec<&Path> = args.input.iter().map(Path::new).collect();
+    if args.copy_assets && !inputs.iter().all(|input| input.is_dir()) {
+        bail!("--copy-assets needs input directories");
+    }
+    let archives: Vec<&Path> = inputs
+        .iter()
+        .copied()
+        .filter(|input| archive::is_zip(input))
+        .collect();
+    if !archives.is_empty() {
+        if args.output.is_none() {
+            bail!("converting a zip archive needs --output");
+        }
+        if args.watch
+            || args.backup
+            || args.backup_dir.is_some()
+            || args.delete_source
+            || args.preserve_timestamps
+        {
+            bail!(
+                "--watch, --backup, --delete-source and --preserve-timestamps can't be used \
+                 with a zip archive"
+            );
+        }
+    }
+
+    let files = collect_files(&inputs, &args)?;
+    let total = files.len();
+    // Files in an archive are read from it, rather than from disk.
+    let (archived, files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|(path, _)| archives.iter().any(|archive| path.starts_with(archive)));
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs.unwrap_or(0))
+        .build()?;
+
+    let start = Instant::now();
+    let mut summary = convert_files(&pool, &files, &args, &options);
+    // With --fail-fast, once a file has failed nothing more is written: not the
+    // archives' notes, the assets or the stats.
+    if args.fail_fast && summary.failed > 0 {
+        summary.skipped += archived.len();
+    } else {
+        for archive in &archives {
+            archive::convert_files(archive, &archived, &args, &options, &mut summary)?;
+        }
+    }
+    summary.log(start.elapsed());
+    if summary.failed > 0 && args.fail_fast {
+        bail!(
+            "stopped at the first failure, leaving {} of {} files unconverted",
+            summary.skipped,
+            total
+        );
+    }
+    if let Some(out_dir) = args.output.as_deref().filter(|_| args.copy_assets) {
+        for input_dir in &inputs {
+            copy_assets(input_dir, Path::new(out_dir), &args)?;
+        }
+    }
+    if let Some(path) = &args.stats_json {
+        let json = serde_json::to_string_pretty(&summary)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    if args.watch {
+        return watch(&inputs, &pool, &args, &options);
+    }
+
+    if summary.failed > 0 {
+        bail!("{} of {} files failed", summary.failed, total);
+    }
+
+    Ok(())
+}
+
+/// Prints how the blocks of each input file, or of stdin, are classified by
+/// the conversion, for `--print-tree`. Nothing is written.
+fn print_tree(args: &Args, options: &ConvertOptions) -> Result<()> {
+    let print = |blocks: &[Block], indent: &str| {
+        for block in blocks {
+            eprintln!("{}{}", indent, block);
+        }
+    };
 
-    let input_dir = Path::new(&args.input);
-    let output_dir = args.output.as_ref().map(Path::new);
+    if args.input.is_empty() {
+        let content = io::read_to_string(io::stdin())?;
+        print(&md2norg::convert_with_stats(&content, options)?.blocks, "");
+        return Ok(());
+    }
+
+    let inputs: Vec<&Path> = args.input.iter().map(Path::new).collect();
+    if inputs.iter().any(|input| archive::is_zip(input)) {
+        bail!("--print-tree can't read zip archives");
+    }
+    for (path, _) in collect_files(&inputs, args)? {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let conversion = md2norg::convert_named(&content, &name, options)
+            .with_context(|| format!("could not convert {}", path.display()))?;
+        eprintln!("{}", path.display());
+        print(&conversion.blocks, "  ");
+    }
+    Ok(())
+}
+
+/// What converting a file did.
+#[derive(Serialize)]
+struct FileReport {
+    input: PathBuf,
+    output: PathBuf,
+    bytes_in: u64,
+    bytes_out: u64,
+    stats: Stats,
+    warnings: Vec<Warning>,
+}
+
+/// The results of converting a set of files.
+#[derive(Default, Serialize)]
+struct Summary {
+    converted: Vec<FileReport>,
+    failed: usize,
+    /// Files left unconverted after a failure, with `--fail-fast`.
+    skipped: usize,
+}
+
+impl Summary {
+    /// Logs totals for the whole run, in verbose mode.
+    fn log(&self, elapsed: Duration) {
+        if !log::log_enabled!(Level::Debug) {
+            return;
+        }
+        let mut stats = Stats::default();
+        for file in &self.converted {
+            stats += file.stats;
+        }
+        log::debug!(
+            "Converted {} files, {} -> {} bytes in {:.2?}",
+            self.converted.len(),
+            self.converted.iter().map(|file| file.bytes_in).sum::<u64>(),
+            self.converted
+                .iter()
+                .map(|file| file.bytes_out)
+                .sum::<u64>(),
+            elapsed
+        );
+        log::debug!(
+            "headings: {}, list items: {}, tasks: {}, links: {}, code blocks: {}, tables: {}",
+            stats.headings,
+            stats.list_items,
+            stats.tasks,
+            stats.links,
+            stats.code_blocks,
+            stats.tables
+        );
+        let warnings: usize = self.converted.iter().map(|file| file.warnings.len()).sum();
+        if warnings > 0 {
+            log::debug!("{} warnings", warnings);
+        }
+    }
+}
+
+/// Finds the markdown files to convert under each of the `inputs`: under it
+/// if it's a directory, itself if it's a file, or the files matching it if
+/// it's a glob pattern, each paired with the path to write it to. Fails if an
+/// input doesn't exist, or if converting would overwrite any of the inputs or
+/// write two files to the same place.
+fn collect_files(inputs: &[&Path], args: &Args) -> Result<Vec<(PathBuf, PathBuf)>> {
+    for input in inputs {
+        // A glob pattern matching nothing is fine, but not one looking in a
+        // directory that isn't there.
+        let path = if is_glob(input) {
+            glob_base(input)
+        } else {
+            input.to_path_buf()
+        };
+        if !path.as_os_str().is_empty() && !path.exists() {
+            bail!("input {} does not exist", path.display());
+        }
+    }
+
+    // Flattened outputs are numbered across all of the inputs.
+    let mut used = HashSet::new();
+    let mut files = Vec::new();
+    for input_dir in inputs {
+        files.extend(find_files(input_dir, args, &mut used)?);
+    }
+    // Inputs can overlap, e.g. a directory and a file in it.
+    let mut seen = HashSet::new();
+    files.retain(|(path, _)| std::path::absolute(path).map_or(true, |path| seen.insert(path)));
+    check_outputs(&files)?;
+    Ok(files)
+}
+
+/// Finds the files for [`collect_files`] in one input, without checking their
+/// outputs.
+fn find_files(
+    input_dir: &Path,
+    args: &Args,
+    used: &mut HashSet<PathBuf>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if archive::is_zip(input_dir) {
+        let files = archive::find_files(input_dir, args, &PathFilter::new(args)?)?;
+        return Ok(output_paths(files, input_dir, args, used));
+    }
+
+    if input_dir.is_file() {
+        return Ok(vec![(
+            input_dir.to_path_buf(),
+            single_output_path(input_dir, args),
+        )]);
+    }
+
+    let filter = PathFilter::new(args)?;
+
+    if is_glob(input_dir) {
+        // Output paths mirror the matches' locations under the pattern's
+        // fixed leading directories.
+        let base = glob_base(input_dir);
+        let pattern = input_dir.to_string_lossy();
+        let mut files = Vec::new();
+        let options = MatchOptions {
+            require_literal_leading_dot: !args.include_hidden,
+            ..Default::default()
+        };
+        for entry in glob::glob_with(&pattern, options).context("invalid glob pattern")? {
+            let path = entry?;
+            if path.is_file() && args.is_markdown(&path) && filter.allows(&path, &base) {
+                files.push(path);
+            }
+        }
+        return Ok(output_paths(files, &base, args, used));
+    }
+
+    let files: Vec<PathBuf> = walk(input_dir, args)
+        .filter(|path| args.is_markdown(path) && filter.allows(path, input_dir))
+        .collect();
 
+    Ok(output_paths(files, input_dir, args, used))
+}
+
+/// The files in `input_dir`, and its subdirectories if recursive, in order.
+fn walk(input_dir: &Path, args: &Args) -> impl Iterator<Item = PathBuf> {
     let walker = if args.recursive {
         WalkDir::new(input_dir)
     } else {
         WalkDir::new(input_dir).max_depth(1)
     };
 
-    for entry in walker.into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "md") {
-            let output_path = if let Some(out_dir) = output_dir {
-                out_dir
-                    .join(path.strip_prefix(input_dir)?)
-                    .with_extension("norg")
+    // Hidden directories, like a vault's `.obsidian` settings, aren't
+    // descended into at all.
+    let include_hidden = args.include_hidden;
+    walker
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |entry| {
+            include_hidden
+                || entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|e| e.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file())
+}
+
+/// Copies the files under `input_dir` that aren't markdown to the matching
+/// location under `out_dir`, for `--copy-assets`.
+fn copy_assets(input_dir: &Path, out_dir: &Path, args: &Args) -> Result<()> {
+    let filter = PathFilter::new(args)?;
+    for path in walk(input_dir, args)
+        .filter(|path| !args.is_markdown(path) && filter.allows(path, input_dir))
+    {
+        let dest = out_dir.join(paths::relative_to(&path, input_dir));
+        if std::path::absolute(&dest)? == std::path::absolute(&path)? {
+            continue;
+        }
+        if args.no_overwrite && dest.exists() {
+            log::info!(
+                "Skipped: {} ({} already exists)",
+                path.display(),
+                dest.display()
+            );
+            continue;
+        }
+        if args.dry_run {
+            println!("Would copy: {} -> {}", path.display(), dest.display());
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("could not create {}", parent.display()))?;
+        }
+        fs::copy(&path, &dest)
+            .with_context(|| format!("could not copy {} to {}", path.display(), dest.display()))?;
+        log::info!("Copied: {} -> {}", path.display(), dest.display());
+    }
+    Ok(())
+}
+
+/// The `--include` and `--exclude` patterns.
+struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    fn new(args: &Args) -> Result<Self> {
+        let compile = |globs: &[String], flag: &str| {
+            globs
+                .iter()
+                .map(|glob| {
+                    Pattern::new(glob).with_context(|| format!("invalid {} pattern {}", flag, glob))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(&args.include, "--include")?,
+            exclude: compile(&args.exclude, "--exclude")?,
+        })
+    }
+
+    /// Whether to convert `path`, matching its path relative to `base`, and
+    /// those of its directories, against the patterns.
+    fn allows(&self, path: &Path, base: &Path) -> bool {
+        let relative = paths::relative_to(path, base);
+        let matches = |patterns: &[Pattern]| {
+            relative
+                .ancestors()
+                .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                .any(|ancestor| {
+                    patterns
+                        .iter()
+                        .any(|pattern| pattern.matches_path(ancestor))
+                })
+        };
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+/// Refuses to convert anything if two files would be written to the same
+/// place, or an output path is also one of the inputs, e.g. converting a file
+/// onto itself, whether with `--output-extension md` or with `.norg` files
+/// being picked up as input with `--ext` and overwritten by another file's
+/// conversion.
+fn check_outputs(files: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let inputs = files
+        .iter()
+        .map(|(path, _)| Ok(std::path::absolute(path)?))
+        .collect::<Result<HashSet<_>>>()?;
+    let mut outputs = HashMap::new();
+    for (path, output_path) in files {
+        let absolute = std::path::absolute(output_path)?;
+        if inputs.contains(&absolute) {
+            bail!(
+                "converting {} would overwrite the input file {}",
+                path.display(),
+                output_path.display()
+            );
+        }
+        if let Some(other) = outputs.insert(absolute, path) {
+            bail!(
+                "both {} and {} would be converted to {}",
+                other.display(),
+                path.display(),
+                output_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Converts `files` in parallel, reporting each failure. With `--fail-fast`,
+/// files not yet started when one fails are skipped.
+fn convert_files(
+    pool: &rayon::ThreadPool,
+    files: &[(PathBuf, PathBuf)],
+    args: &Args,
+    options: &ConvertOptions,
+) -> Summary {
+    let bar = progress_bar(files.len(), args);
+    let stop = AtomicBool::new(false);
+    let results: Vec<_> = pool.install(|| {
+        files
+            .par_iter()
+            .map(|(path, output_path)| {
+                if stop.load(Ordering::Relaxed) {
+                    return None;
+                }
+                bar.set_message(path.display().to_string());
+                let result = process_file(path, output_path, args, options, &bar);
+                bar.inc(1);
+                if let Err(err) = &result {
+                    bar.suspend(|| log::error!("Failed to convert {}: {:#}", path.display(), err));
+                    if args.fail_fast {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+                Some(result)
+            })
+            .collect()
+    });
+    bar.finish_and_clear();
+
+    let mut summary = Summary::default();
+    for result in results {
+        match result {
+            Some(Ok(Some(report))) => summary.converted.push(report),
+            Some(Ok(None)) => {}
+            Some(Err(_)) => summary.failed += 1,
+            None => summary.skipped += 1,
+        }
+    }
+    summary
+}
+
+/// A progress bar for converting `len` files, drawn on stderr when stdout is
+/// a terminal and not asked to be quiet. Anything printed while it's drawn
+/// has to go through [`ProgressBar::suspend`] to keep the two apart.
+fn progress_bar(len: usize, args: &Args) -> ProgressBar {
+    if args.quiet || !io::stdout().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(ProgressStyle::with_template("{bar:40} {pos}/{len} {wide_msg}").unwrap());
+    bar
+}
+
+/// Re-converts markdown files under the `inputs` as they're created or
+/// changed, until the process is interrupted.
+fn watch(
+    inputs: &[&Path],
+    pool: &rayon::ThreadPool,
+    args: &Args,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for input_dir in inputs {
+        // A glob pattern can match files anywhere under its leading
+        // directories.
+        let (watched, mode) = if is_glob(input_dir) {
+            let base = glob_base(input_dir);
+            let base = if base.as_os_str().is_empty() {
+                PathBuf::from(".")
             } else {
-                path.with_extension("norg")
+                base
             };
+            (base, RecursiveMode::Recursive)
+        } else if args.recursive {
+            (input_dir.to_path_buf(), RecursiveMode::Recursive)
+        } else {
+            (input_dir.to_path_buf(), RecursiveMode::NonRecursive)
+        };
+        watcher
+            .watch(&watched, mode)
+            .with_context(|| format!("could not watch {}", watched.display()))?;
+        log::info!("Watching {} for changes", input_dir.display());
+    }
 
-            if let Some(parent) = output_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+    // The watcher holds the sender, so this only ends if it fails.
+    while let Ok(event) = rx.recv() {
+        let mut changed = HashSet::new();
+        add_changed_paths(&mut changed, event);
+        // Editors often save a file in several steps, so wait for the events
+        // to settle before converting anything.
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            add_changed_paths(&mut changed, event);
+        }
 
-            let content = fs::read_to_string(path)?;
-            let converted = convert_markdown_to_neorg(&content)?;
+        // Output paths depend on the rest of the tree when flattening, so are
+        // worked out afresh from all of the files.
+        let files: Vec<_> = collect_files(inputs, args)?
+            .into_iter()
+            .filter(|(path, _)| {
+                path.canonicalize()
+                    .is_ok_and(|path| changed.contains(&path))
+            })
+            .collect();
+        convert_files(pool, &files, args, options);
+    }
 
-            fs::write(&output_path, converted)?;
+    Ok(())
+}
+
+/// Whether `input` is a glob pattern rather than a path that exists.
+fn is_glob(input: &Path) -> bool {
+    !input.exists() && input.to_string_lossy().contains(['*', '?', '['])
+}
 
-            println!("Converted: {} -> {}", path.display(), output_path.display());
+/// The leading directories of a glob `pattern` that don't contain wildcards.
+fn glob_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect()
+}
+
+fn add_changed_paths(changed: &mut HashSet<PathBuf>, event: notify::Result<notify::Event>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            changed.extend(
+                event
+                    .paths
+                    .iter()
+                    .filter_map(|path| path.canonicalize().ok()),
+            );
         }
+        Ok(_) => {}
+        Err(err) => log::error!("Watch error: {}", err),
     }
+}
 
-    Ok(())
+/// The output path for a single input file: `--output` itself, unless it's an
+/// existing directory to convert the file into, or next to the input.
+fn single_output_path(input: &Path, args: &Args) -> PathBuf {
+    match args.output.as_deref().map(Path::new) {
+        Some(out_dir) if out_dir.is_dir() => out_dir
+            .join(input.file_name().unwrap_or_default())
+            .with_extension(&args.output_extension),
+        Some(output) => output.to_path_buf(),
+        None => input.with_extension(&args.output_extension),
+    }
+}
+
+/// Pairs each markdown file with the path its conversion is written to: next
+/// to it, at the matching location under the output directory, or, when
+/// flattening, directly in the output directory.
+fn output_paths(
+    files: Vec<PathBuf>,
+    input_dir: &Path,
+    args: &Args,
+    used: &mut HashSet<PathBuf>,
+) -> Vec<(PathBuf, PathBuf)> {
+    let out_dir = args.output.as_deref().map(Path::new);
+
+    if args.flatten {
+        let out_dir = out_dir.unwrap_or(input_dir);
+        return files
+            .into_iter()
+            .map(|path| {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let mut output_path = out_dir.join(format!("{}.{}", stem, args.output_extension));
+                // Notes with the same name from different directories get a
+                // numbered suffix, in the order they're found.
+                let mut n = 1;
+                while !used.insert(output_path.clone()) {
+                    n += 1;
+                    output_path = out_dir.join(format!("{}-{}.{}", stem, n, args.output_extension));
+                }
+                (path, output_path)
+            })
+            .collect();
+    }
+
+    files
+        .into_iter()
+        .map(|path| {
+            let output_path = match out_dir {
+                Some(out_dir) => out_dir.join(paths::relative_to(&path, input_dir)),
+                None => path.clone(),
+            };
+            let output_path = output_path.with_extension(&args.output_extension);
+            (path, output_path)
+        })
+        .collect()
 }
 
-fn convert_markdown_to_neorg(content: &str) -> Result<String> {
-    let mut result = String::new();
-
-    // Convert headings
-    let heading_regex = Regex::new(r"^(#+)\s+(.*)$").unwrap();
-
-    let link_conversions = [
-        // Image link with title (must come before basic image link)
-        (r#"!\[([^\]]*)\]\(([^)]+)\s+"([^"]+)"\)"#, "{image:$2}[$1]"),
-        // Basic image link
-        (r"!\[([^\]]*)\]\(([^)]+)\)", "{image:$2}[$1]"),
-        // Reference-style image link
-        (r"!\[([^\]]*)\]\[([^\]]*)\]", "{image:$2}[$1]"),
-        // Basic Markdown link
-        (r"\[([^\]]+)\]\(([^)]+)\)", "{$2}[$1]"),
-        // Reference-style link
-        (r"\[([^\]]+)\]\[([^\]]*)\]", "{$2}[$1]"),
-        // Obsidian links
-        (r"\[\[([^\]]+)\]\]", "{:$1.norg:}"),
-        // Reference-style link definition
-        (
-            r#"(?m)^\[([^\]]+)\]:\s*(\S+)(?:\s+"([^"]+)")?"#,
-            "@$1 $2 $3",
-        ),
-        // Automatic links
-        (r"<(https?://[^>]+)>", "{$1}[$1]"),
-    ];
-
-    let mut content = content.to_string();
-    for (pattern, replacement) in link_conversions.iter() {
-        let re = Regex::new(pattern).unwrap();
-        content = re.replace_all(&content, *replacement).to_string();
-    }
-
-    for line in content.lines() {
-        if let Some(caps) = heading_regex.captures(line) {
-            let level = caps[1].len();
-            let text = &caps[2];
-            result.push_str(&format!("{} {}\n", "*".repeat(level), text));
-        } else if let Some(caps) = Regex::new(r"^(\s*)- \[ \] (.*)$").unwrap().captures(line) {
-            let indent = &caps[1];
-            let text = &caps[2];
-            result.push_str(&format!("{}-- ( ) {}\n", indent, text));
-        } else if let Some(caps) = Regex::new(r"^(\s*)- \[x\] (.*)$").unwrap().captures(line) {
-            let indent = &caps[1];
-            let text = &caps[2];
-            result.push_str(&format!("{}-- (x) {}\n", indent, text));
-        } else if let Some(caps) = Regex::new(r"^(\s*)[-*+]\s+(.*)$").unwrap().captures(line) {
-            let indent = &caps[1];
-            let text = &caps[2];
-            result.push_str(&format!("{}-- {}\n", indent, text));
+/// Converts a single markdown file, writing the result to `output_path`.
+/// Returns what was done, unless the file was skipped or this is a dry run.
+fn process_file(
+    path: &Path,
+    output_path: &Path,
+    args: &Args,
+    options: &ConvertOptions,
+    bar: &ProgressBar,
+) -> Result<Option<FileReport>> {
+    let bytes = fs::read(path).map_err(|source| ConvertError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    convert_source(path, bytes, output_path, args, options, bar)
+}
+
+/// Converts `bytes`, the markdown file at `path` on disk or in an archive, as
+/// [`process_file`] does, so that files are converted the same either way.
+fn convert_source(
+    path: &Path,
+    bytes: Vec<u8>,
+    output_path: &Path,
+    args: &Args,
+    options: &ConvertOptions,
+    bar: &ProgressBar,
+) -> Result<Option<FileReport>> {
+    if args.no_overwrite && output_path.exists() {
+        if args.dry_run {
+            bar.suspend(|| {
+                println!(
+                    "Would skip: {} ({} already exists)",
+                    path.display(),
+                    output_path.display()
+                )
+            });
         } else {
-            result.push_str(line);
-            result.push('\n');
+            bar.suspend(|| {
+                log::info!(
+                    "Skipped: {} ({} already exists)",
+                    path.display(),
+                    output_path.display()
+                )
+            });
         }
+        return Ok(None);
     }
 
-    // Convert code blocks
-    let code_block_regex = Regex::new(r"```(\w*)\n([\s\S]*?)```").unwrap();
-    let result = code_block_regex.replace_all(&result, |caps: &regex::Captures| {
-        let language = &caps[1];
-        let code = &caps[2].trim_end(); // Trim trailing whitespace
-        format!("@code {}\n{}\n@end", language, code)
-    });
+    let start = Instant::now();
+    let bytes_in = bytes.len() as u64;
+    let content = String::from_utf8(bytes).map_err(|source| ConvertError::Utf8 {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    if args.dry_run {
+        // Strictly, a file that would fail to convert is reported as failing.
+        if options.strict {
+            md2norg::convert_named(&content, &name, options)?;
+        }
+        let collision = if output_path.exists() {
+            " (overwrites existing file)"
+        } else {
+            ""
+        };
+        bar.suspend(|| {
+            if let Some(backup) = backup_path(path, args) {
+                println!("Would back up: {} -> {}", path.display(), backup.display());
+            }
+            println!(
+                "Would convert: {} -> {}{}",
+                path.display(),
+                output_path.display(),
+                collision
+            )
+        });
+        return Ok(None);
+    }
 
-    Ok(result.to_string())
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_convert_headings() -> Result<()> {
-        let markdown = "# Heading 1\n## Heading 2\n### Heading 3";
-        let expected = "* Heading 1\n** Heading 2\n*** Heading 3\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_code_blocks() -> Result<()> {
-        let markdown = "```rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n```";
-        let expected = "@code rust\nfn main() {\n    println!(\"Hello, world!\");\n}\n@end\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_lists() -> Result<()> {
-        let markdown = "- Item 1\n- Item 2\n  - Subitem 2.1\n- Item 3";
-        let expected = "-- Item 1\n-- Item 2\n  -- Subitem 2.1\n-- Item 3\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_todos() -> Result<()> {
-        let markdown = "- [ ] Todo item\n- [x] Completed item";
-        let expected = "-- ( ) Todo item\n-- (x) Completed item\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_mixed_content() -> Result<()> {
-        let markdown = "# Main Heading\n\n## Subheading\n\n- List item 1\n- [ ] Todo item\n\n```python\nprint(\"Hello, world!\")\n```";
-        let expected = "* Main Heading\n\n** Subheading\n\n-- List item 1\n-- ( ) Todo item\n\n@code python\nprint(\"Hello, world!\")\n@end\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_preserve_non_converted_content() -> Result<()> {
-        let markdown = "This is regular text.\n\nIt should be preserved as-is.";
-        let expected = "This is regular text.\n\nIt should be preserved as-is.\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_obsidian_links() -> Result<()> {
-        let markdown = "Check out [[My Page]] and [[Another Page With Spaces]]";
-        let expected = "Check out {:My Page.norg:} and {:Another Page With Spaces.norg:}\n";
-        assert_eq!(convert_markdown_to_neorg(markdown)?, expected);
-        Ok(())
-    }
-
-    #[test]
-    fn test_convert_markdown_links() -> Result<()> {
-        let input = r#"
-[Basic link](https://example.com)
-[Reference link][ref]
-[Implicit reference link][]
-<https://example.com>
-![Image](image.jpg)
-![Image with title](image.jpg "Title")
-![Reference image][img-ref]
-
-[ref]: https://example.com "Reference Title"
-[img-ref]: image.jpg "Image Reference Title"
-"#;
-
-        let expected_output = r#"
-{https://example.com}[Basic link]
-{ref}[Reference link]
-{}[Implicit reference link]
-{https://example.com}[https://example.com]
-{image:image.jpg}[Image]
-{image:image.jpg}[Image with title]
-{image:img-ref}[Reference image]
-
-@ref https://example.com Reference Title
-@img-ref image.jpg Image Reference Title
-"#;
-
-        let actual = convert_markdown_to_neorg(input)?;
-        println!("{}", &actual);
-        assert_eq!(actual, expected_output);
-        Ok(())
+    if let Some(backup) = backup_path(path, args) {
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("could not create {}", parent.display()))?;
+        }
+        fs::copy(path, &backup).with_context(|| {
+            format!(
+                "could not back up {} to {}",
+                path.display(),
+                backup.display()
+            )
+        })?;
+        bar.suspend(|| log::debug!("Backed up: {} -> {}", path.display(), backup.display()));
+    }
+
+    let conversion = md2norg::convert_named(&content, &name, options)?;
+    // `create_dir_all` tolerates the directory appearing concurrently, so
+    // files in the same directory can be converted in parallel.
+    let write_error = |source| ConvertError::Write {
+        path: output_path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(write_error)?;
+    }
+    fs::write(output_path, &conversion.output).map_err(write_error)?;
+    let report = FileReport {
+        input: path.to_path_buf(),
+        output: output_path.to_path_buf(),
+        bytes_in,
+        bytes_out: conversion.output.len() as u64,
+        stats: conversion.stats,
+        warnings: conversion.warnings,
+    };
+    if args.preserve_timestamps {
+        preserve_metadata(path, output_path)
+            .with_context(|| format!("could not update {}", output_path.display()))?;
+    }
+    if args.delete_source {
+        fs::remove_file(path).with_context(|| format!("could not delete {}", path.display()))?;
     }
+
+    if log::log_enabled!(Level::Debug) {
+        let elapsed = start.elapsed();
+        bar.suspend(|| {
+            log::debug!(
+                "Converted: {} -> {} ({} -> {} bytes in {:.2?})",
+                path.display(),
+                output_path.display(),
+                report.bytes_in,
+                report.bytes_out,
+                elapsed
+            )
+        });
+    } else {
+        bar.suspend(|| log::info!("Converted: {} -> {}", path.display(), output_path.display()));
+    }
+    bar.suspend(|| log_warnings(path, &report.warnings));
+
+    Ok(Some(report))
+}
+
+/// Logs the warnings for the file at `path`, in verbose mode.
+fn log_warnings(path: &Path, warnings: &[Warning]) {
+    if !log::log_enabled!(Level::Debug) {
+        return;
+    }
+    for warning in warnings {
+        log::warn!("{}:{}: {}", path.display(), warning.line, warning.message);
+    }
+}
+
+/// Where to save a copy of the markdown file at `path` before converting it,
+/// if backups are wanted: under `--backup-dir` at its place under the input
+/// it was found in, or next to it with a `.bak` suffix.
+fn backup_path(path: &Path, args: &Args) -> Option<PathBuf> {
+    if let Some(backup_dir) = &args.backup_dir {
+        let root = args
+            .input
+            .iter()
+            .map(Path::new)
+            .map(|input| match input {
+                input if is_glob(input) => glob_base(input),
+                input if input.is_file() => input.parent().unwrap_or(input).to_path_buf(),
+                input => input.to_path_buf(),
+            })
+            .find(|root| path.starts_with(root));
+        let relative = match root {
+            Some(root) => paths::relative_to(path, &root),
+            None => PathBuf::from(path.file_name().unwrap_or_default()),
+        };
+        return Some(backup_dir.join(relative));
+    }
+    args.backup.then(|| {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        PathBuf::from(backup)
+    })
+}
+
+/// Copies the modification time and permissions of `source` onto `output`.
+fn preserve_metadata(source: &Path, output: &Path) -> Result<()> {
+    let metadata = fs::metadata(source)?;
+    filetime::set_file_mtime(output, FileTime::from_last_modification_time(&metadata))?;
+    fs::set_permissions(output, metadata.permissions())?;
+    Ok(())
 }
@@ -0,0 +1,210 @@
+//! Settings that control how a document is converted.
+
+/// The Markdown task markers understood by default, and the Neorg TODO status
+/// each becomes.
+///
+/// Obsidian themes and plugins use the character between a task's brackets
+/// for more states than done and undone, so the common ones are mapped to
+/// their closest Neorg counterpart.
+pub const DEFAULT_TODO_STATUSES: &[(char, char)] = &[
+    (' ', ' '), // undone
+    ('x', 'x'), // done
+    ('X', 'x'),
+    ('/', '-'), // in progress -> pending
+    ('-', '_'), // cancelled
+    ('?', '?'), // ambiguous
+    ('!', '!'), // important
+    ('=', '='), // on hold
+    ('+', '+'), // recurring
+    ('_', '_'),
+];
+
+/// Code block languages written differently in Markdown from the names Neorg
+/// highlights them by, and the name each becomes.
+pub const DEFAULT_LANGUAGES: &[(&str, &str)] = &[
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+    ("c++", "cpp"),
+    ("cs", "c_sharp"),
+    ("c#", "c_sharp"),
+    ("yml", "yaml"),
+    ("md", "markdown"),
+];
+
+/// Settings for [`convert_with_options`](crate::convert_with_options).
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Maps the character between a Markdown task's brackets (the `/` in
+    /// `- [/]`) to the Neorg TODO status it becomes (the `-` in `- (-)`).
+    /// Tasks using a character that isn't listed are kept as plain list items.
+    pub todo_statuses: Vec<(char, char)>,
+
+    /// Maps a code block's language, as written after its opening fence, to
+    /// the name it's given in Neorg. Matched case-insensitively, and languages
+    /// that aren't listed are kept as written.
+    pub languages: Vec<(String, String)>,
+
+    /// Point links to other Markdown notes, `[Note](note.md)`, at their Neorg
+    /// conversion, `{:note.norg:}`.
+    pub rewrite_md_links: bool,
+
+    /// Collect Obsidian `#tags` into the document's `categories` metadata.
+    pub tags: bool,
+
+    /// Turn the Obsidian Tasks plugin's emoji dates and priorities on a task
+    /// (`📅 2024-01-01`, `⏫`) into Neorg TODO extensions (`< 2024-01-01`,
+    /// `# B`).
+    pub task_metadata: bool,
+
+    /// Escape characters in the text that Neorg would read as markup, such as
+    /// the slashes in `/usr/local/`, so they show up as written. The markup
+    /// made by the conversions is left alone.
+    pub escape_text: bool,
+
+    /// Decode HTML entities, such as `&amp;`, `&copy;` and `&#8212;`, outside
+    /// of code to the characters they stand for.
+    pub decode_entities: bool,
+
+    /// Replace the typographic punctuation word processors insert, curly
+    /// quotes, en and em dashes and ellipses, with its plain ASCII
+    /// equivalent.
+    pub normalize_punctuation: bool,
+
+    /// Convert raw inline HTML: `<br>` becomes a line break, `<b>`, `<i>` and
+    /// the like become Neorg markup, and the tags of other HTML elements are
+    /// removed, keeping their content.
+    pub html: bool,
+
+    /// Always emit a `@document.meta` block with a `title`, taken from the
+    /// frontmatter, the first `# Heading`, or, when converting a file, its
+    /// name.
+    pub add_meta: bool,
+
+    /// Collapse runs of blank lines outside of code blocks down to one.
+    pub collapse_blank_lines: bool,
+
+    /// Reflow paragraphs to lines of at most this many characters, where the
+    /// words allow. Headings, lists, tables and code are left as they are.
+    pub wrap: Option<usize>,
+
+    /// Fail with [`ConvertError::Malformed`](crate::ConvertError::Malformed)
+    /// on the first of the document's
+    /// [`warnings`](crate::Conversion::warnings), rather than converting it
+    /// anyway.
+    pub strict: bool,
+
+    /// Which of the major conversions are run. Markdown syntax a disabled
+    /// pass would have converted is left as it is.
+    pub passes: Passes,
+
+    /// The line endings to write.
+    pub line_ending: LineEnding,
+}
+
+/// Line endings for the converted document.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Whichever of `\n` and `\r\n` most of the input's lines end with.
+    #[default]
+    Keep,
+    Lf,
+    Crlf,
+}
+
+/// The major conversion passes, each of which can be turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passes {
+    /// ATX (`# Heading`) and setext headings.
+    pub headings: bool,
+    /// Bulleted and numbered lists.
+    pub lists: bool,
+    /// `- [ ]` tasks, which are otherwise left to the lists pass.
+    pub todos: bool,
+    /// Markdown, reference, autolinks and Obsidian links, and images.
+    pub links: bool,
+    /// Fenced and indented code blocks.
+    pub code: bool,
+    /// Bold, italic, strikethrough, highlights, and sub- and superscripts.
+    pub emphasis: bool,
+    /// Pipe tables.
+    pub tables: bool,
+}
+
+impl Default for Passes {
+    fn default() -> Self {
+        Self {
+            headings: true,
+            lists: true,
+            todos: true,
+            links: true,
+            code: true,
+            emphasis: true,
+            tables: true,
+        }
+    }
+}
+
+impl Passes {
+    /// No passes at all, to turn on only the ones wanted.
+    pub fn none() -> Self {
+        Self {
+            headings: false,
+            lists: false,
+            todos: false,
+            links: false,
+            code: false,
+            emphasis: false,
+            tables: false,
+        }
+    }
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            todo_statuses: DEFAULT_TODO_STATUSES.to_vec(),
+            languages: DEFAULT_LANGUAGES
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            rewrite_md_links: true,
+            tags: true,
+            task_metadata: false,
+            escape_text: false,
+            decode_entities: false,
+            normalize_punctuation: false,
+            html: true,
+            add_meta: false,
+            collapse_blank_lines: false,
+            wrap: None,
+            strict: false,
+            passes: Passes::default(),
+            line_ending: LineEnding::default(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// The Neorg TODO status for a Markdown task marker, if it has one.
+    pub(crate) fn todo_status(&self, marker: char) -> Option<char> {
+        self.todo_statuses
+            .iter()
+            .find(|(from, _)| *from == marker)
+            .map(|(_, to)| *to)
+    }
+
+    /// The Neorg name for a code block's language.
+    pub(crate) fn language<'a>(&'a self, language: &'a str) -> &'a str {
+        self.languages
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(language))
+            .map_or(language, |(_, to)| to)
+    }
+}
@@ -0,0 +1,86 @@
+//! Working out where a file is relative to the input it was found in.
+
+use std::path::{Path, PathBuf};
+
+/// The path of `path` relative to `base`, the input it was found under.
+///
+/// When they don't share a prefix as written, e.g. one is relative and the
+/// other absolute, or on Windows one has a `\\?\` prefix and the other
+/// doesn't, they're compared again in their absolute, normalised forms.
+/// Failing that, the file name is used alone, so the file is still written
+/// somewhere sensible.
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return relative.to_path_buf();
+    }
+    match normalize(path).strip_prefix(normalize(base)) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => PathBuf::from(path.file_name().unwrap_or_default()),
+    }
+}
+
+/// `path` made absolute, which on Windows also makes its separators
+/// consistent.
+fn normalize(path: &Path) -> PathBuf {
+    let path = without_verbatim_prefix(path);
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// `path` without a Windows verbatim prefix: `\\?\C:\notes` becomes
+/// `C:\notes`, and `\\?\UNC\server\share` becomes `\\server\share`.
+fn without_verbatim_prefix(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", rest))
+    } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_to() {
+        let relative = relative_to(Path::new("notes/sub/a.md"), Path::new("notes"));
+        assert_eq!(relative, Path::new("sub/a.md"));
+
+        let base = std::path::absolute("notes").unwrap();
+        let relative = relative_to(Path::new("notes/sub/a.md"), &base);
+        assert_eq!(relative, Path::new("sub/a.md"));
+    }
+
+    #[test]
+    fn test_relative_to_falls_back_to_file_name() {
+        let relative = relative_to(Path::new("/elsewhere/a.md"), Path::new("/notes"));
+        assert_eq!(relative, Path::new("a.md"));
+    }
+
+    #[test]
+    fn test_without_verbatim_prefix() {
+        assert_eq!(
+            without_verbatim_prefix(Path::new(r"\\?\C:\notes")),
+            PathBuf::from(r"C:\notes")
+        );
+        assert_eq!(
+            without_verbatim_prefix(Path::new(r"\\?\UNC\server\share\notes")),
+            PathBuf::from(r"\\server\share\notes")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_relative_to_windows_paths() {
+        let relative = relative_to(Path::new(r"\\?\C:\notes\sub\a.md"), Path::new("C:/notes"));
+        assert_eq!(relative, Path::new(r"sub\a.md"));
+
+        let relative = relative_to(
+            Path::new(r"\\server\share\notes\a.md"),
+            Path::new(r"\\?\UNC\server\share\notes"),
+        );
+        assert_eq!(relative, Path::new("a.md"));
+    }
+}
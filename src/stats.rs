@@ -0,0 +1,28 @@
+//! Counts of what a conversion did.
+
+use std::ops::AddAssign;
+
+use serde::Serialize;
+
+/// How many of each kind of construct a conversion converted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    pub headings: usize,
+    pub list_items: usize,
+    pub tasks: usize,
+    /// Links and images of every kind: Markdown, reference and Obsidian.
+    pub links: usize,
+    pub code_blocks: usize,
+    pub tables: usize,
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Self) {
+        self.headings += other.headings;
+        self.list_items += other.list_items;
+        self.tasks += other.tasks;
+        self.links += other.links;
+        self.code_blocks += other.code_blocks;
+        self.tables += other.tables;
+    }
+}
@@ -0,0 +1,104 @@
+//! GitHub-style pipe tables.
+//!
+//! A table is a header row, a delimiter row of dashes (optionally with `:`
+//! alignment markers, which Neorg has no equivalent for and are dropped), and
+//! any number of body rows. Neorg's `@table` tag takes one row per line with
+//! cells separated by `|`, and a line of `-` between the header and the body.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static DELIMITER_CELL_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^:?-+:?$").unwrap());
+
+/// Converts the table starting at the first of `lines`, if there is one.
+/// Returns the Neorg table and the number of lines it was made from.
+pub(crate) fn convert(lines: &[&str]) -> Option<(String, usize)> {
+    let (header, delimiter) = match lines {
+        [header, delimiter, ..] => (*header, *delimiter),
+        _ => return None,
+    };
+    if !header.contains('|') || !is_delimiter_row(delimiter) {
+        return None;
+    }
+    let header = split_row(header);
+    if header.len() != split_row(delimiter).len() {
+        return None;
+    }
+
+    let mut table = String::from("@table\n");
+    table.push_str(&header.join(" | "));
+    table.push_str("\n-\n");
+
+    let body: Vec<_> = lines[2..]
+        .iter()
+        .take_while(|line| line.contains('|') && !line.trim().is_empty())
+        .collect();
+    for row in &body {
+        table.push_str(&split_row(row).join(" | "));
+        table.push('\n');
+    }
+    table.push_str("@end\n");
+
+    Some((table, body.len() + 2))
+}
+
+/// The number of cells in the header and delimiter rows starting at
+/// `header`, if they'd make a table but for having different numbers of
+/// cells, so aren't converted.
+pub(crate) fn mismatched_columns(header: &str, delimiter: &str) -> Option<(usize, usize)> {
+    if !header.contains('|') || !is_delimiter_row(delimiter) {
+        return None;
+    }
+    let columns = (split_row(header).len(), split_row(delimiter).len());
+    (columns.0 != columns.1).then_some(columns)
+}
+
+fn is_delimiter_row(line: &str) -> bool {
+    line.contains('|')
+        && split_row(line)
+            .iter()
+            .all(|cell| DELIMITER_CELL_REGEX.is_match(cell))
+}
+
+/// Splits a row into its trimmed cells, ignoring the optional outer pipes.
+/// Escaped pipes (`\|`) are kept, escaped, within their cell.
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push_str("\\|");
+                chars.next();
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_row() {
+        assert_eq!(split_row("| a | b \\| c |"), vec!["a", "b \\| c"]);
+        assert_eq!(split_row("a|b"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_not_a_table() {
+        assert_eq!(convert(&["a | b", "just text"]), None);
+        assert_eq!(convert(&["a | b", "---"]), None);
+        assert_eq!(convert(&["a | b | c", "--- | ---"]), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Warnings about Markdown that can't be converted faithfully.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    closes_fence, normalize_label, starts_indented_code, strip_code_indent, table, ConvertOptions,
+    References, CODE_SPAN_REGEX, DIV_FENCE_REGEX, HEADING_REGEX, HTML_ELEMENTS, HTML_TAG_REGEX,
+    MAX_HEADING_LEVEL, OPEN_FENCE_REGEX, REFERENCE_LINK_REGEX,
+};
+
+/// Something in a document that the conversion couldn't carry over as it
+/// was meant, such as raw HTML with no Neorg equivalent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    /// The 1-based line of the input it's on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// HTML tags that are converted to Neorg markup, on top of the elements whose
+/// tags are dropped, keeping their content.
+const CONVERTED_TAGS: &[&str] = &["b", "strong", "i", "em", "u", "br"];
+
+/// Checks the body of a document, which starts on line `first_line` of the
+/// input, for constructs that won't convert faithfully. Code is skipped, as
+/// it's copied as it is.
+pub(crate) fn check(
+    body: &str,
+    first_line: usize,
+    references: &References,
+    options: &ConvertOptions,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut warn = |line: usize, message: String| {
+        warnings.push(Warning {
+            line: first_line + line,
+            message,
+        })
+    };
+
+    // The lines since the last code block, to tell indented code from text
+    // indented under a list item as `split_regions` does.
+    let mut prose: Vec<&str> = Vec::new();
+    let mut fence: Option<(usize, &str)> = None;
+    let mut indented_code = false;
+    let lines: Vec<&str> = body.lines().collect();
+    for (n, &line) in lines.iter().enumerate() {
+        if let Some((_, open)) = fence {
+            if closes_fence(line, open) {
+                fence = None;
+            }
+            continue;
+        }
+        if indented_code && (strip_code_indent(line).is_some() || line.trim().is_empty()) {
+            continue;
+        }
+        indented_code = false;
+        if strip_code_indent(line).is_some() && starts_indented_code(&prose) {
+            indented_code = true;
+            prose.clear();
+            continue;
+        }
+        if let Some(caps) = OPEN_FENCE_REGEX.captures(line) {
+            fence = Some((n, caps.get(2).unwrap().as_str()));
+            prose.clear();
+            continue;
+        }
+        prose.push(line);
+
+        if DIV_FENCE_REGEX.is_match(line) {
+            let class = line
+                .trim_start()
+                .trim_start_matches(':')
+                .trim_matches(|c: char| c.is_whitespace() || matches!(c, '{' | '}' | '.'));
+            if !class.is_empty() {
+                warn(
+                    n,
+                    format!(
+                        "Neorg has nothing like the `{}` div, so only its content is kept",
+                        class
+                    ),
+                );
+            }
+            continue;
+        }
+
+        if let Some(caps) = HEADING_REGEX
+            .captures(line)
+            .filter(|caps| caps[1].len() > MAX_HEADING_LEVEL && options.passes.headings)
+        {
+            warn(
+                n,
+                format!(
+                    "level {} heading is made level {}, the deepest Neorg has",
+                    caps[1].len(),
+                    MAX_HEADING_LEVEL
+                ),
+            );
+        }
+
+        if let Some((header, delimiter)) = lines
+            .get(n + 1)
+            .and_then(|next| table::mismatched_columns(line, next))
+            .filter(|_| options.passes.tables)
+        {
+            warn(
+                n,
+                format!(
+                    "table has {} header cells but {} in its delimiter row, so is left as text",
+                    header, delimiter
+                ),
+            );
+        }
+
+        let text = CODE_SPAN_REGEX.replace_all(line, "");
+        if options.html {
+            let mut tags: Vec<String> = Vec::new();
+            for caps in HTML_TAG_REGEX.captures_iter(&text) {
+                let name = caps[1].to_lowercase();
+                let converted = CONVERTED_TAGS.contains(&name.as_str())
+                    || HTML_ELEMENTS.contains(&name.as_str());
+                let tag = format!("`<{}>`", name);
+                if !converted && !caps[0].starts_with("</") && !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            if !tags.is_empty() {
+                warn(
+                    n,
+                    format!(
+                        "raw HTML {} can't be converted, so is left as it is",
+                        tags.join(", ")
+                    ),
+                );
+            }
+        }
+        if options.passes.links {
+            for caps in REFERENCE_LINK_REGEX.captures_iter(&text) {
+                let label = if caps[3].is_empty() {
+                    &caps[2]
+                } else {
+                    &caps[3]
+                };
+                if !label.is_empty() && !references.contains_key(&normalize_label(label)) {
+                    warn(
+                        n,
+                        format!(
+                            "no definition for the reference `{}`, so it's left as it is",
+                            label
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some((n, _)) = fence {
+        warn(
+            n,
+            "code fence is never closed, so runs to the end of the document".to_string(),
+        );
+    }
+    warnings
+}
@@ -0,0 +1,1268 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn md2norg(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_md2norg"))
+        .args(args)
+        .output()
+        .expect("failed to run md2norg")
+}
+
+fn path_arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Copies the fixture notes into `dir`, so they can be converted in place.
+fn copy_fixture_notes(dir: &Path) {
+    let notes = fixtures().join("notes");
+    for entry in walkdir::WalkDir::new(&notes) {
+        let entry = entry.unwrap();
+        let dest = dir.join(entry.path().strip_prefix(&notes).unwrap());
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(dest).unwrap();
+        } else {
+            fs::copy(entry.path(), dest).unwrap();
+        }
+    }
+}
+
+/// Asserts that each of the `expected` fixtures (relative paths to the
+/// expected `.norg` files) was written to `dir` with the expected content.
+fn assert_converted(dir: &Path, expected: &[&str]) {
+    for name in expected {
+        assert_eq!(
+            fs::read_to_string(dir.join(name)).unwrap(),
+            fs::read_to_string(fixtures().join("expected").join(name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_fixtures_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    copy_fixture_notes(dir.path());
+
+    let result = md2norg(&["--input", path_arg(dir.path())]);
+
+    assert!(result.status.success());
+    assert_converted(dir.path(), &["index.norg", "reading list.norg"]);
+    assert!(!dir.path().join("projects/md2norg.norg").exists());
+}
+
+#[test]
+fn test_fixtures_recursive() {
+    let dir = tempfile::tempdir().unwrap();
+    copy_fixture_notes(dir.path());
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--recursive"]);
+
+    assert!(result.status.success());
+    assert_converted(
+        dir.path(),
+        &["index.norg", "reading list.norg", "projects/md2norg.norg"],
+    );
+    assert!(!dir.path().join("projects/ignored.norg").exists());
+}
+
+#[test]
+fn test_fixtures_glob_input() {
+    let output = tempfile::tempdir().unwrap();
+    let pattern = fixtures().join("notes/**/[im]*.md");
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(&pattern),
+        "--output",
+        path_arg(output.path()),
+    ]);
+
+    assert!(result.status.success());
+    assert_converted(output.path(), &["index.norg", "projects/md2norg.norg"]);
+    assert!(!output.path().join("reading list.norg").exists());
+}
+
+#[test]
+fn test_fixtures_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected = ["index.norg", "reading list.norg", "projects/md2norg.norg"];
+
+    // However many threads convert them, the files come out the same.
+    for jobs in ["1", "4"] {
+        let output = dir.path().join(jobs);
+        let result = md2norg(&[
+            "--input",
+            path_arg(&fixtures().join("notes")),
+            "--output",
+            path_arg(&output),
+            "--recursive",
+            "--jobs",
+            jobs,
+        ]);
+        assert!(result.status.success());
+        assert_converted(&output, &expected);
+    }
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--jobs", "0"]);
+    assert_eq!(result.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("--jobs"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_no_progress_bar_off_terminal() {
+    // The tests' output is piped, so the progress bar is hidden even without
+    // --quiet, leaving only the log, which --quiet hides too.
+    let notes = fixtures().join("notes");
+    for quiet in [None, Some("--quiet")] {
+        let output = tempfile::tempdir().unwrap();
+        let mut args = vec![
+            "--input",
+            path_arg(&notes),
+            "--output",
+            path_arg(output.path()),
+            "--recursive",
+        ];
+        args.extend(quiet);
+        let result = md2norg(&args);
+        assert!(result.status.success());
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(
+            stderr.lines().all(|line| line.starts_with("Converted: ")),
+            "stderr: {}",
+            stderr
+        );
+        assert!(!stderr.contains("3/3") && !stderr.contains('\r'));
+        assert_eq!(stderr.is_empty(), quiet.is_some(), "stderr: {}", stderr);
+    }
+}
+
+#[test]
+fn test_glob_input_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["2023-01.md", "2023-02.md", "2024-01.md", "2023-notes.txt"] {
+        fs::write(dir.path().join(name), "# Note\n").unwrap();
+    }
+
+    let result = md2norg(&["--input", path_arg(&dir.path().join("2023-*"))]);
+
+    assert!(result.status.success());
+    assert!(dir.path().join("2023-01.norg").exists());
+    assert!(dir.path().join("2023-02.norg").exists());
+    assert!(!dir.path().join("2024-01.norg").exists());
+    assert!(!dir.path().join("2023-notes.norg").exists());
+}
+
+#[test]
+fn test_fixtures_stats_json() {
+    let dir = tempfile::tempdir().unwrap();
+    copy_fixture_notes(dir.path());
+    let stats_path = dir.path().join("stats.json");
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--recursive",
+        "--stats-json",
+        path_arg(&stats_path),
+    ]);
+
+    assert!(result.status.success());
+    let stats: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["failed"], 0);
+    let files = stats["converted"].as_array().unwrap();
+    for name in ["index", "reading list", "projects/md2norg"] {
+        let input = dir.path().join(format!("{}.md", name));
+        let file = files
+            .iter()
+            .find(|file| file["input"] == path_arg(&input))
+            .unwrap_or_else(|| panic!("no entry for {}: {}", name, stats));
+        assert_eq!(
+            file["output"],
+            path_arg(&dir.path().join(format!("{}.norg", name)))
+        );
+        assert_eq!(file["bytes_in"], fs::metadata(&input).unwrap().len());
+        assert!(file["stats"]["headings"].as_u64().unwrap() > 0, "{}", file);
+    }
+    assert_eq!(files.len(), 3);
+}
+
+#[test]
+fn test_fixtures_to_output_directory() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    copy_fixture_notes(input.path());
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(&output.path().join("converted")),
+        "--recursive",
+    ]);
+
+    assert!(result.status.success());
+    assert_converted(
+        &output.path().join("converted"),
+        &["index.norg", "reading list.norg", "projects/md2norg.norg"],
+    );
+    assert!(!input.path().join("index.norg").exists());
+}
+
+#[test]
+fn test_zip_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("vault.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, content) in [
+        ("vault/index.md", "# Index\n\nSee [[Projects]]\n"),
+        ("vault/projects/md2norg.md", "- [ ] Convert zips\n"),
+        ("vault/.obsidian/notes.md", "# Settings\n"),
+        ("vault/img/map.png", "not really a png"),
+    ] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let output = dir.path().join("norg");
+    let result = md2norg(&["--input", path_arg(&archive), "--output", path_arg(&output)]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(output.join("vault/index.norg")).unwrap(),
+        "* Index\n\nSee {:Projects.norg:}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("vault/projects/md2norg.norg")).unwrap(),
+        "- ( ) Convert zips\n"
+    );
+    assert!(!output.join("vault/.obsidian").exists());
+    assert!(!output.join("vault/img").exists());
+
+    // Without an output directory there's nowhere to write them.
+    let result = md2norg(&["--input", path_arg(&archive)]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("converting a zip archive needs --output"),
+        "stderr: {}",
+        stderr
+    );
+
+    // The notes in an archive have no modification time on disk to keep.
+    let result = md2norg(&[
+        "--input",
+        path_arg(&archive),
+        "--output",
+        path_arg(&output),
+        "--preserve-timestamps",
+    ]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("--preserve-timestamps can't be used with a zip archive"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_zip_input_dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("vault.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, content) in [("good.md", "# Good\n"), ("bad.md", "```sh\nls\n")] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    let output = dir.path().join("norg");
+    fs::create_dir(&output).unwrap();
+    fs::write(output.join("good.norg"), "kept").unwrap();
+
+    // Files in an archive are reported as files on disk are, including those
+    // that would fail to convert strictly.
+    let args = ["--input", path_arg(&archive), "--output", path_arg(&output)];
+    let result = md2norg(&[&args[..], &["--dry-run", "--no-overwrite"]].concat());
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("Would skip: "), "stdout: {}", stdout);
+    assert!(stdout.contains("Would convert: "), "stdout: {}", stdout);
+    assert!(stdout.contains("bad.norg"), "stdout: {}", stdout);
+
+    let result = md2norg(&[&args[..], &["--dry-run", "--strict"]].concat());
+    assert_eq!(result.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stdout.contains("Would convert: "), "stdout: {}", stdout);
+    assert!(!stdout.contains("bad.norg"), "stdout: {}", stdout);
+    assert!(stderr.contains("bad.md"), "stderr: {}", stderr);
+
+    assert_eq!(
+        fs::read_to_string(output.join("good.norg")).unwrap(),
+        "kept"
+    );
+    assert!(!output.join("bad.norg").exists());
+}
+
+#[test]
+fn test_missing_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing");
+
+    let output = md2norg(&["--input", path_arg(&missing)]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = format!("input {} does not exist", missing.display());
+    assert!(stderr.contains(&message), "stderr: {}", stderr);
+
+    let pattern = missing.join("*.md");
+    let output = md2norg(&["--input", path_arg(&pattern)]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&message), "stderr: {}", stderr);
+
+    // A pattern in a directory that's there is fine, even matching nothing.
+    let pattern = dir.path().join("*.md");
+    let output = md2norg(&["--input", path_arg(&pattern)]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_multiple_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let work = dir.path().join("work");
+    let home = dir.path().join("home");
+    fs::create_dir_all(work.join("meetings")).unwrap();
+    fs::create_dir(&home).unwrap();
+    fs::write(work.join("meetings/standup.md"), "# Standup\n").unwrap();
+    fs::write(home.join("garden.md"), "# Garden\n").unwrap();
+
+    let output = dir.path().join("norg");
+    let result = md2norg(&[
+        "--input",
+        path_arg(&work),
+        "--input",
+        path_arg(&home),
+        "--output",
+        path_arg(&output),
+        "--recursive",
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(output.join("meetings/standup.norg")).unwrap(),
+        "* Standup\n"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("garden.norg")).unwrap(),
+        "* Garden\n"
+    );
+
+    // In place, each is written next to its own markdown.
+    let result = md2norg(&["-i", path_arg(&work), "-i", path_arg(&home), "-r"]);
+    assert!(result.status.success());
+    assert!(work.join("meetings/standup.norg").exists());
+    assert!(home.join("garden.norg").exists());
+
+    // Two notes that would be written to the same place are refused.
+    fs::write(home.join("standup.md"), "# Other\n").unwrap();
+    let result = md2norg(&[
+        "--input",
+        path_arg(&work.join("meetings")),
+        "--input",
+        path_arg(&home),
+        "--output",
+        path_arg(&output),
+    ]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("would be converted to"),
+        "stderr: {}",
+        stderr
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("garden.norg")).unwrap(),
+        "* Garden\n"
+    );
+}
+
+#[test]
+fn test_copy_assets() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    fs::create_dir_all(input.path().join("notes/img")).unwrap();
+    fs::create_dir(input.path().join(".obsidian")).unwrap();
+    fs::write(input.path().join("notes/trip.md"), "![Map](img/map.png)\n").unwrap();
+    fs::write(input.path().join("notes/img/map.png"), b"\x89PNG").unwrap();
+    fs::write(input.path().join("notes/plan.pdf"), "%PDF").unwrap();
+    fs::write(input.path().join(".obsidian/app.json"), "{}").unwrap();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(output.path()),
+        "--recursive",
+        "--copy-assets",
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(output.path().join("notes/trip.norg")).unwrap(),
+        "{image:img/map.png}[Map]\n"
+    );
+    assert_eq!(
+        fs::read(output.path().join("notes/img/map.png")).unwrap(),
+        b"\x89PNG"
+    );
+    assert!(output.path().join("notes/plan.pdf").exists());
+    assert!(!output.path().join("notes/trip.md").exists());
+    assert!(!output.path().join(".obsidian").exists());
+
+    // Without it, only the notes are written.
+    let output = tempfile::tempdir().unwrap();
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(output.path()),
+        "--recursive",
+    ]);
+    assert!(result.status.success());
+    assert!(!output.path().join("notes/img").exists());
+}
+
+#[test]
+fn test_continues_past_unreadable_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("good.md"), "# Good\n").unwrap();
+    fs::write(dir.path().join("bad.md"), [0xff, 0xfe, 0x00]).unwrap();
+
+    let output = md2norg(&["--input", path_arg(dir.path())]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("bad.md"), "stderr: {}", stderr);
+    assert!(stderr.contains("1 of 2 files failed"), "stderr: {}", stderr);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("good.norg")).unwrap(),
+        "* Good\n"
+    );
+    assert!(!dir.path().join("bad.norg").exists());
+}
+
+#[test]
+fn test_print_tree() {
+    let dir = tempfile::tempdir().unwrap();
+    let note = dir.path().join("note.md");
+    fs::write(&note, "## Setup\n\n```rust\nfn main() {}\n```\n").unwrap();
+
+    let output = md2norg(&["--input", path_arg(&note), "--print-tree"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("heading level 2: Setup"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(
+        stderr.contains("code block lang=rust (1 line)"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(output.stdout.is_empty());
+    assert!(!dir.path().join("note.norg").exists());
+}
+
+#[test]
+fn test_completions() {
+    let output = md2norg(&["completions", "bash"]);
+    assert!(output.status.success());
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(!script.is_empty());
+    assert!(script.contains("--input"), "script: {}", script);
+
+    let output = md2norg(&["completions", "tcsh"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_fail_fast() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.md"), [0xff, 0xfe, 0x00]).unwrap();
+    fs::write(dir.path().join("b.md"), "# B\n").unwrap();
+    fs::write(dir.path().join("c.md"), "# C\n").unwrap();
+
+    // On one thread, the files are converted in order, so the rest are left.
+    let output = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--jobs",
+        "1",
+        "--fail-fast",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("a.md"), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("stopped at the first failure, leaving 2 of 3 files unconverted"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(!dir.path().join("b.norg").exists());
+    assert!(!dir.path().join("c.norg").exists());
+
+    // Without it, the others are converted and the failure counted.
+    let output = md2norg(&["--input", path_arg(dir.path()), "--jobs", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 of 3 files failed"), "stderr: {}", stderr);
+    assert!(dir.path().join("b.norg").exists());
+    assert!(dir.path().join("c.norg").exists());
+}
+
+#[test]
+fn test_fail_fast_copies_no_assets() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    fs::write(input.path().join("a.md"), [0xff, 0xfe, 0x00]).unwrap();
+    fs::write(input.path().join("map.png"), b"\x89PNG").unwrap();
+    let stats = output.path().join("stats.json");
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(output.path()),
+        "--fail-fast",
+        "--copy-assets",
+        "--stats-json",
+        path_arg(&stats),
+    ]);
+    assert_eq!(result.status.code(), Some(1));
+    assert!(!output.path().join("map.png").exists());
+    assert!(!stats.exists());
+}
+
+#[test]
+fn test_strict_skips_malformed_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("good.md"), "# Good\n").unwrap();
+    fs::write(dir.path().join("bad.md"), "# Bad\n\n```sh\nls\n").unwrap();
+
+    let output = md2norg(&["--input", path_arg(dir.path()), "--strict", "--dry-run"]);
+    assert!(!output.status.success());
+    assert!(!dir.path().join("good.norg").exists());
+
+    let output = md2norg(&["--input", path_arg(dir.path()), "--strict"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("malformed markdown at line 3: code fence is never closed"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(stderr.contains("1 of 2 files failed"), "stderr: {}", stderr);
+    assert!(dir.path().join("good.norg").exists());
+    assert!(!dir.path().join("bad.norg").exists());
+
+    // Without it, the unclosed fence is converted to the end of the file.
+    let output = md2norg(&["--input", path_arg(dir.path())]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("bad.norg")).unwrap(),
+        "* Bad\n\n@code bash\nls\n@end\n"
+    );
+}
+
+#[test]
+fn test_dry_run_writes_nothing() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    fs::create_dir(input.path().join("sub")).unwrap();
+    fs::write(input.path().join("new.md"), "# New\n").unwrap();
+    fs::write(input.path().join("sub/existing.md"), "# Existing\n").unwrap();
+    fs::create_dir(output.path().join("sub")).unwrap();
+    fs::write(output.path().join("sub/existing.norg"), "hand edited").unwrap();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(output.path()),
+        "--recursive",
+        "--dry-run",
+    ]);
+
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("Would convert:"), "stdout: {}", stdout);
+    assert!(
+        stdout.contains("existing.norg (overwrites existing file)"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(!output.path().join("new.norg").exists());
+    assert_eq!(
+        fs::read_to_string(output.path().join("sub/existing.norg")).unwrap(),
+        "hand edited"
+    );
+}
+
+#[test]
+fn test_no_overwrite_keeps_existing_output() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("new.md"), "# New\n").unwrap();
+    fs::write(dir.path().join("existing.md"), "# Existing\n").unwrap();
+    fs::write(dir.path().join("existing.norg"), "hand edited").unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--no-overwrite"]);
+
+    assert!(result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Skipped:"), "stderr: {}", stderr);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("existing.norg")).unwrap(),
+        "hand edited"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("new.norg")).unwrap(),
+        "* New\n"
+    );
+}
+
+#[test]
+fn test_in_place_same_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("a.md"), "# A\n").unwrap();
+    fs::write(dir.path().join("sub/b.md"), "# B\n").unwrap();
+
+    let args = [
+        "--input",
+        path_arg(dir.path()),
+        "--output",
+        path_arg(dir.path()),
+        "--recursive",
+    ];
+    assert!(md2norg(&args).status.success());
+    assert!(dir.path().join("a.md").exists());
+    assert!(dir.path().join("sub/b.norg").exists());
+
+    // A second run over the same directory only reads the markdown again.
+    let result = md2norg(&[&args[..], &["--delete-source"]].concat());
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.norg")).unwrap(),
+        "* A\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("sub/b.norg")).unwrap(),
+        "* B\n"
+    );
+    assert!(!dir.path().join("a.md").exists());
+    assert!(!dir.path().join("sub/b.md").exists());
+}
+
+#[test]
+fn test_delete_source_only_after_success() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("good.md"), "# Good\n").unwrap();
+    fs::write(dir.path().join("bad.md"), "# Bad\n").unwrap();
+    // A directory in the way of the output makes the write fail.
+    fs::create_dir(dir.path().join("bad.norg")).unwrap();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--delete-source",
+        "--dry-run",
+    ]);
+    assert!(result.status.success());
+    assert!(dir.path().join("good.md").exists());
+    assert!(!dir.path().join("good.norg").exists());
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--delete-source"]);
+    assert!(!result.status.success());
+    assert!(dir.path().join("good.norg").exists());
+    assert!(!dir.path().join("good.md").exists());
+    assert!(dir.path().join("bad.md").exists());
+}
+
+#[test]
+fn test_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("a.md"), "# A\n").unwrap();
+    fs::write(dir.path().join("sub/b.md"), "# B\n").unwrap();
+    fs::write(dir.path().join("sub/b.norg"), "* Kept\n").unwrap();
+
+    let args = ["--input", path_arg(dir.path()), "--recursive"];
+    let result = md2norg(&[&args[..], &["--backup", "--dry-run"]].concat());
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("Would back up:"), "stdout: {}", stdout);
+    assert!(!dir.path().join("a.md.bak").exists());
+
+    // A skipped file is left alone, so needs no backup.
+    let result = md2norg(
+        &[
+            &args[..],
+            &["--backup", "--no-overwrite", "--delete-source"],
+        ]
+        .concat(),
+    );
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.md.bak")).unwrap(),
+        "# A\n"
+    );
+    assert!(!dir.path().join("a.md").exists());
+    assert!(dir.path().join("a.norg").exists());
+    assert!(!dir.path().join("sub/b.md.bak").exists());
+
+    let backups = dir.path().join("backups");
+    let result = md2norg(&[&args[..], &["--backup-dir", path_arg(&backups)]].concat());
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(backups.join("sub/b.md")).unwrap(),
+        "# B\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("sub/b.norg")).unwrap(),
+        "* B\n"
+    );
+}
+
+#[test]
+fn test_refuses_to_overwrite_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("note.md"), "# Note\n").unwrap();
+    fs::write(dir.path().join("note.norg"), "* Note\n").unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--ext", "md,norg"]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("would overwrite the input file"),
+        "stderr: {}",
+        stderr
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("note.norg")).unwrap(),
+        "* Note\n"
+    );
+
+    let note = dir.path().join("note.md");
+    let result = md2norg(&["--input", path_arg(&note), "--output", path_arg(&note)]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&note).unwrap(), "# Note\n");
+}
+
+#[test]
+fn test_output_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+    fs::write(dir.path().join("a.md"), "# A\n").unwrap();
+    fs::write(dir.path().join("sub/a.md"), "# Sub A\n").unwrap();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--recursive",
+        "--output-extension",
+        ".org",
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.org")).unwrap(),
+        "* A\n"
+    );
+    assert!(dir.path().join("sub/a.org").exists());
+    assert!(!dir.path().join("a.norg").exists());
+
+    let out = dir.path().join("flat");
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--output",
+        path_arg(&out),
+        "--recursive",
+        "--flatten",
+        "--output-extension",
+        "neorg.txt",
+    ]);
+    assert!(result.status.success());
+    assert!(out.join("a.neorg.txt").exists());
+    assert!(out.join("a-2.neorg.txt").exists());
+
+    // Converting in place to the input's own extension would overwrite it.
+    let result = md2norg(&["--input", path_arg(dir.path()), "--output-extension", "md"]);
+    assert!(!result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.md")).unwrap(),
+        "# A\n"
+    );
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--output-extension", "a/b"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_include_and_exclude() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in [
+        "index.md",
+        "templates/daily.md",
+        "templates/meetings/weekly.md",
+        "journal/2024-01-01.md",
+        "journal/private.md",
+    ] {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "# Note\n").unwrap();
+    }
+    let converted = |name: &str| dir.path().join(name).with_extension("norg").exists();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--recursive",
+        "--exclude",
+        "templates",
+    ]);
+    assert!(result.status.success());
+    assert!(converted("index.md"));
+    assert!(converted("journal/2024-01-01.md"));
+    assert!(!converted("templates/daily.md"));
+    assert!(!converted("templates/meetings/weekly.md"));
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--recursive",
+        "--include",
+        "journal",
+        "--include",
+        "templates/*/*.md",
+        "--exclude",
+        "*private*",
+        "--dry-run",
+    ]);
+    assert!(result.status.success());
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("2024-01-01.md"), "stdout: {}", stdout);
+    assert!(stdout.contains("weekly.md"), "stdout: {}", stdout);
+    assert!(!stdout.contains("index.md"), "stdout: {}", stdout);
+    assert!(!stdout.contains("daily.md"), "stdout: {}", stdout);
+    assert!(!stdout.contains("private.md"), "stdout: {}", stdout);
+}
+
+#[test]
+fn test_skips_hidden_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join(".obsidian/plugins")).unwrap();
+    fs::write(dir.path().join(".obsidian/plugins/README.md"), "# Plugin\n").unwrap();
+    fs::write(dir.path().join("note.md"), "# Note\n").unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--recursive"]);
+    assert!(result.status.success());
+    assert!(dir.path().join("note.norg").exists());
+    assert!(!dir.path().join(".obsidian/plugins/README.norg").exists());
+
+    let pattern = dir.path().join("**/*.md");
+    let result = md2norg(&["--input", path_arg(&pattern), "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(!stdout.contains("README.md"), "stdout: {}", stdout);
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--recursive",
+        "--include-hidden",
+    ]);
+    assert!(result.status.success());
+    assert!(dir.path().join(".obsidian/plugins/README.norg").exists());
+}
+
+#[test]
+fn test_converts_all_markdown_extensions() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.md", "b.markdown", "c.mdown", "d.mkd", "e.mdx", "f.txt"] {
+        fs::write(dir.path().join(name), "# Title\n").unwrap();
+    }
+
+    let result = md2norg(&["--input", path_arg(dir.path())]);
+
+    assert!(result.status.success());
+    for name in ["a", "b", "c", "d", "e"] {
+        assert!(dir.path().join(name).with_extension("norg").exists());
+    }
+    assert!(!dir.path().join("f.norg").exists());
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--ext", "txt"]);
+
+    assert!(result.status.success());
+    assert!(dir.path().join("f.norg").exists());
+}
+
+#[test]
+fn test_quiet_and_verbose_reporting() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("note.md"), "# Note\n").unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path())]);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Converted:"), "stderr: {}", stderr);
+    assert!(result.stdout.is_empty());
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--quiet"]);
+    assert!(result.status.success());
+    assert!(result.stdout.is_empty());
+    assert!(result.stderr.is_empty());
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--verbose"]);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("7 -> 7 bytes"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_log_levels_and_colors() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("good.md"), "# Good\n").unwrap();
+    fs::write(dir.path().join("bad.md"), "# Bad\n").unwrap();
+    fs::create_dir(dir.path().join("bad.norg")).unwrap();
+
+    for flags in [&["-q"][..], &[], &["-v"], &["-vv"], &["-vvv", "--no-color"]] {
+        let result = md2norg(&[&["--input", path_arg(dir.path())][..], flags].concat());
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(!result.status.success(), "{:?}", flags);
+        assert!(
+            stderr.contains("error: Failed to convert"),
+            "{:?}: {}",
+            flags,
+            stderr
+        );
+        assert_eq!(
+            stderr.contains("Converted:"),
+            flags != ["-q"],
+            "{:?}: {}",
+            flags,
+            stderr
+        );
+        assert!(!stderr.contains('\x1b'), "{:?}: {}", flags, stderr);
+    }
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--force-color"]);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("\x1b["), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_verbose_summary() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("a.md"),
+        "# A\n\n[home](https://example.com)\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("b.md"),
+        "# B\n\n## C\n\n```rust\nfn main() {}\n```\n",
+    )
+    .unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--verbose"]);
+    assert!(result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Converted 2 files"), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("headings: 3, list items: 0, tasks: 0, links: 1, code blocks: 1"),
+        "stderr: {}",
+        stderr
+    );
+
+    let result = md2norg(&["--input", path_arg(dir.path())]);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(!stderr.contains("headings:"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_verbose_warnings() {
+    let dir = tempfile::tempdir().unwrap();
+    let note = dir.path().join("note.md");
+    fs::write(&note, "# Note\n\n<iframe src=\"x\"></iframe>\n").unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--verbose"]);
+    assert!(result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let warning = format!(
+        "warn: {}:3: raw HTML `<iframe>` can't be converted",
+        note.display()
+    );
+    assert!(stderr.contains(&warning), "stderr: {}", stderr);
+
+    let result = md2norg(&["--input", path_arg(dir.path())]);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(!stderr.contains("warn"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_flatten_output() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    fs::create_dir_all(input.path().join("a/deep")).unwrap();
+    fs::create_dir(input.path().join("b")).unwrap();
+    fs::write(input.path().join("top.md"), "# Top\n").unwrap();
+    fs::write(input.path().join("a/deep/notes.md"), "# A\n").unwrap();
+    fs::write(input.path().join("b/notes.md"), "# B\n").unwrap();
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(input.path()),
+        "--output",
+        path_arg(output.path()),
+        "--recursive",
+        "--flatten",
+    ]);
+
+    assert!(result.status.success());
+    let mut names: Vec<_> = fs::read_dir(output.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["notes-2.norg", "notes.norg", "top.norg"]);
+    assert_eq!(
+        fs::read_to_string(output.path().join("notes.norg")).unwrap(),
+        "* A\n"
+    );
+    assert_eq!(
+        fs::read_to_string(output.path().join("notes-2.norg")).unwrap(),
+        "* B\n"
+    );
+}
+
+#[test]
+fn test_single_file_to_named_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("note.md");
+    let output = dir.path().join("out/renamed.norg");
+    fs::create_dir(dir.path().join("out")).unwrap();
+    fs::write(&input, "# Note\n").unwrap();
+
+    let result = md2norg(&["--input", path_arg(&input), "--output", path_arg(&output)]);
+
+    assert!(result.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), "* Note\n");
+    assert!(!dir.path().join("note.norg").exists());
+
+    // An existing directory as the output gets the file converted into it.
+    let result = md2norg(&[
+        "--input",
+        path_arg(&input),
+        "--output",
+        path_arg(dir.path().join("out").as_path()),
+    ]);
+
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("out/note.norg")).unwrap(),
+        "* Note\n"
+    );
+}
+
+#[test]
+fn test_watch_reconverts_changed_files() {
+    let input = tempfile::tempdir().unwrap();
+    let output = tempfile::tempdir().unwrap();
+    fs::write(input.path().join("note.md"), "# First\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md2norg"))
+        .args([
+            "--input",
+            path_arg(input.path()),
+            "--output",
+            path_arg(output.path()),
+            "--watch",
+        ])
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run md2norg");
+
+    // Wait for the watcher to be set up before changing anything.
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    while !line.starts_with("Watching") {
+        line.clear();
+        assert_ne!(stderr.read_line(&mut line).unwrap(), 0, "md2norg exited");
+    }
+    thread::spawn(move || std::io::copy(&mut stderr, &mut std::io::sink()));
+
+    fs::write(input.path().join("note.md"), "# Second\n").unwrap();
+    fs::write(input.path().join("new.md"), "# New\n").unwrap();
+
+    let read = |name: &str| fs::read_to_string(output.path().join(name)).unwrap_or_default();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while (read("note.norg") != "* Second\n" || read("new.norg") != "* New\n")
+        && Instant::now() < deadline
+    {
+        thread::sleep(Duration::from_millis(50));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(read("note.norg"), "* Second\n");
+    assert_eq!(read("new.norg"), "* New\n");
+}
+
+#[test]
+fn test_config_file_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("notes/sub")).unwrap();
+    fs::write(
+        dir.path().join("notes/sub/note.mkd"),
+        "# Note #idea\n\n\n\nText\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("notes/skipped.md"), "# Skipped\n").unwrap();
+    fs::write(
+        dir.path().join("md2norg.toml"),
+        "recursive = true\noutput = \"out\"\nextensions = [\"mkd\"]\n\n[conversions]\ntags = false\ncollapse_blank_lines = true\n",
+    )
+    .unwrap();
+
+    let result = Command::new(env!("CARGO_BIN_EXE_md2norg"))
+        .args(["--input", "notes"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("out/sub/note.norg")).unwrap(),
+        "* Note #idea\n\nText\n"
+    );
+    assert!(!dir.path().join("out/skipped.norg").exists());
+}
+
+#[test]
+fn test_flags_override_config_file() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("note.md"), "Some #idea\n").unwrap();
+    let config = dir.path().join("settings.toml");
+    fs::write(
+        &config,
+        "output = \"unused\"\n\n[conversions]\ntags = false\n",
+    )
+    .unwrap();
+    let output = dir.path().join("out");
+
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--output",
+        path_arg(&output),
+        "--config",
+        path_arg(&config),
+    ]);
+
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(output.join("note.norg")).unwrap(),
+        "Some #idea\n"
+    );
+    assert!(!dir.path().join("unused").exists());
+
+    fs::write(&config, "recursion = true\n").unwrap();
+    let result = md2norg(&[
+        "--input",
+        path_arg(dir.path()),
+        "--config",
+        path_arg(&config),
+    ]);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("invalid config"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_language_mappings() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("note.md"),
+        "```sh\nls\n```\n```js\nf()\n```\n",
+    )
+    .unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--language", "sh=zsh"]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("note.norg")).unwrap(),
+        "@code zsh\nls\n@end\n@code javascript\nf()\n@end\n"
+    );
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--language", "sh"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_skip_and_only_passes() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("note.md"),
+        "# Title\n\n- [link](x.md) and **bold**\n",
+    )
+    .unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--skip", "links,emphasis"]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("note.norg")).unwrap(),
+        "* Title\n\n- [link](x.md) and **bold**\n"
+    );
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--only", "headings"]);
+    assert!(result.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("note.norg")).unwrap(),
+        "* Title\n\n- [link](x.md) and **bold**\n"
+    );
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--skip", "nonsense"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_preserve_timestamps() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("old.md");
+    fs::write(&input, "# Old note\n").unwrap();
+    let mtime = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+    filetime::set_file_mtime(&input, mtime).unwrap();
+
+    let result = md2norg(&["--input", path_arg(dir.path()), "--preserve-timestamps"]);
+
+    assert!(result.status.success());
+    let output = fs::metadata(dir.path().join("old.norg")).unwrap();
+    assert_eq!(
+        filetime::FileTime::from_last_modification_time(&output),
+        mtime
+    );
+    assert_eq!(
+        output.permissions(),
+        fs::metadata(&input).unwrap().permissions()
+    );
+}
@@ -0,0 +1,44 @@
+//! Converts each `input.md` under `tests/fixtures/conversions`, checking the
+//! output against the `expected.norg` beside it. Adding a case is a matter of
+//! adding a directory with the two files.
+//!
+//! Fixtures convert with the default options, so a case that needs any other
+//! option, or checks more than the output, like warnings, is tested in
+//! `src/lib.rs` instead.
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_conversion_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/conversions");
+    let mut cases: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        let name = case.file_name().unwrap().to_string_lossy();
+        let input = fs::read_to_string(case.join("input.md")).unwrap();
+        let expected = fs::read_to_string(case.join("expected.norg")).unwrap();
+        match md2norg::convert(&input) {
+            Ok(output) if output == expected => {}
+            Ok(output) => failures.push(format!(
+                "{}:\n--- expected\n{}\n--- got\n{}",
+                name, expected, output
+            )),
+            Err(err) => failures.push(format!("{}: {}", name, err)),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} fixtures failed\n\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n\n")
+    );
+}